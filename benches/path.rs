@@ -0,0 +1,92 @@
+// cargo bench --bench path
+use std::hint::black_box;
+use std::time::Instant;
+
+use bevy::ecs::system::{CommandQueue, Commands};
+use bevy::prelude::{IVec3, World};
+
+use magic_game::client_plugin::NoiseChunkGen;
+use magic_game::voxel::{self, ChunkGenerator, VoxelId, Voxels};
+use magic_game::voxel::{CHUNK_SIZE_CB, CHUNK_SIZE_I32};
+use magic_game::voxel::path::{find_path, Agent, MAX_FALL, STEP_HEIGHT};
+
+const SIZE: i32 = 128;
+const RUNS: usize = 20;
+
+// the game's own noise terrain, holes and drops and all, generated without
+// the rest of the app. the chunk entities go in a throwaway world
+fn generate(chunks: i32) -> Voxels {
+    let mut voxels = Voxels::default();
+    voxel::register_default_voxels(&mut voxels);
+
+    let mut world = World::new();
+    let mut queue = CommandQueue::default();
+    let mut generator = NoiseChunkGen::default();
+    for x in 0..chunks {
+        for z in 0..chunks {
+            voxels.add_chunk(Commands::new(&mut queue, &world), x, 0, z);
+            let mut chunk: Box<[VoxelId; CHUNK_SIZE_CB]> =
+                vec![VoxelId::air(); CHUNK_SIZE_CB]
+                .into_boxed_slice()
+                .try_into()
+                .unwrap();
+            generator.generate(x, 0, z, &voxels, &mut chunk);
+            voxels.fill_chunk(x, 0, z, chunk);
+        }
+    }
+    queue.apply(&mut world);
+    voxels
+}
+
+// every spot an agent can stand on, on a coarse grid over the map
+fn spots(voxels: &Voxels, agent: Agent) -> Vec<IVec3> {
+    let mut spots = Vec::new();
+    for x in (0..=SIZE).step_by(4) {
+        for z in (0..=SIZE).step_by(4) {
+            spots.extend((0..CHUNK_SIZE_I32)
+                .map(|y| IVec3::new(x, y, z))
+                .filter(|&pos| agent.can_stand(voxels, pos)));
+        }
+    }
+    spots
+}
+
+fn closest(spots: &[IVec3], x: i32, z: i32) -> Option<IVec3> {
+    spots.iter()
+        .copied()
+        .min_by_key(|p| (p.x - x).pow(2) + (p.z - z).pow(2))
+}
+
+fn main() {
+    let voxels = generate(SIZE / CHUNK_SIZE_I32 + 1);
+    let agent = Agent::new(2.0, STEP_HEIGHT, MAX_FALL);
+    let spots = spots(&voxels, agent);
+    let start = closest(&spots, 0, SIZE / 2).expect("nowhere to stand");
+
+    // the noise leaves holes and islands all over, so each route goes to
+    // the closest spot to its end that can actually be reached
+    let reachable: Vec<_> = spots.iter()
+        .copied()
+        .filter(|&goal| find_path(&voxels, agent, start, goal).is_some())
+        .collect();
+
+    for (name, x, z) in [
+        ("short", 16, SIZE / 2 + 16),
+        ("across", SIZE, SIZE / 2),
+        ("diagonal", SIZE, 0),
+    ] {
+        let Some(goal) = closest(&reachable, x, z)
+        else {
+            println!("{name}: nowhere reachable");
+            continue;
+        };
+
+        let path = find_path(&voxels, agent, start, goal).unwrap();
+        let begin = Instant::now();
+        for _ in 0..RUNS {
+            black_box(find_path(black_box(&voxels), agent, start, goal));
+        }
+        let each = begin.elapsed() / RUNS as u32;
+        println!("{name}: {} steps in {each:?}", path.len());
+    }
+}
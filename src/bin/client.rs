@@ -1,16 +1,33 @@
 use bevy::log::LogPlugin;
+use clap::Parser;
 use magic_game::net::protocol::{MessageUsi, MyChannel};
 use magic_game::*;
+use magic_game::magic::assets::SpellAssetPlugin;
 use magic_game::client::*;
-use net::client;
+use net::{client, NetArgs, NetSettings, NetSide};
+use net::client::ClientNetPlugin;
 use net::protocol::shared_config;
 
 fn main() {
+    let args = NetArgs::parse();
+    let settings = NetSettings::from_args(&args, NetSide::Client)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+
     App::new()
         .add_plugins(MinimalPlugins)
         .add_plugins(LogPlugin::default())
-        .add_plugins(client::client_plugin(shared_config()))
+        .add_plugins(AssetPlugin {
+            watch_for_changes_override: Some(cfg!(debug_assertions)),
+            ..default()
+        })
+        .add_plugins(SpellAssetPlugin)
+        .add_plugins(client::client_plugin(
+            shared_config(&settings, Mode::Separate), &settings))
         .add_plugins(net::ProtocolPlugin)
+        .add_plugins(ClientNetPlugin)
         .add_systems(Startup, init)
         .add_systems(Update, (on_connect, on_disconnect, on_message))
         .run();
@@ -1,31 +1,42 @@
 use bevy::log::LogPlugin;
+use clap::Parser;
 use magic_game::net::protocol::{MessageUsi, MyChannel};
 use magic_game::*;
+use magic_game::magic::assets::SpellAssetPlugin;
 use magic_game::server::*;
-use net::server;
+use net::{server, NetArgs, NetSettings, NetSide};
+use net::server::ServerNetPlugin;
 use net::protocol::shared_config;
 
 fn main() {
+    let args = NetArgs::parse();
+    let settings = NetSettings::from_args(&args, NetSide::Server)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+
     App::new()
         .add_plugins(MinimalPlugins)
         .add_plugins(LogPlugin::default())
-        .add_plugins(server::server_plugin(shared_config()))
+        .add_plugins(AssetPlugin {
+            watch_for_changes_override: Some(cfg!(debug_assertions)),
+            ..default()
+        })
+        .add_plugins(SpellAssetPlugin)
+        .add_plugins(server::server_plugin(
+            shared_config(&settings, Mode::Separate), &settings))
         .add_plugins(net::ProtocolPlugin)
+        .add_plugins(ServerNetPlugin)
+        .insert_resource(settings)
         .add_systems(Startup, init)
-        .add_systems(Update, (on_connect, on_disconnect, on_message))
+        .add_systems(Update, on_message)
         .run();
 }
 
-fn init(mut commands: Commands) {
+fn init(mut commands: Commands, settings: Res<NetSettings>) {
     commands.start_server();
-}
-
-fn on_connect(
-    mut connections: EventReader<ConnectEvent>
-) {
-    for c in connections.read() {
-        info!("{} has connected to the server", c.client_id);
-    }
+    info!("listening on {}", settings.server.bind);
 }
 
 fn on_message(
@@ -42,11 +53,3 @@ fn on_message(
         };
     }
 }
-
-fn on_disconnect(
-    mut disconnects: EventReader<DisconnectEvent>
-) {
-    for d in disconnects.read() {
-        info!("{} has disconnected from the server:", d.client_id);
-    }
-}
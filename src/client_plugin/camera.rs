@@ -0,0 +1,175 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::*;
+use client_plugin::Player;
+use client_plugin::settings::VideoSettings;
+use client_plugin::movement::{
+    MoveMode, PlayerMovement, CROUCH_EYE_HEIGHT, EYE_HEIGHT, WALK_SPEED};
+
+// just short of straight up or down, so the view can't flip over
+pub const MAX_PITCH: f32 = FRAC_PI_2 - 0.0175;
+
+// how far the camera moves up and down, and how many bobs per unit walked
+pub const BOB_HEIGHT: f32 = 0.05;
+pub const BOB_FREQUENCY: f32 = 0.35;
+// how quickly it fades in and out when starting and stopping, per second
+const BOB_EASE: f32 = 8.0;
+
+// how far behind the player the third person camera sits, and how close it
+// gets to whatever it's pushed up against
+pub const ORBIT_DISTANCE: f32 = 6.0;
+pub const ORBIT_MARGIN: f32 = 0.2;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    // orbiting behind the player
+    ThirdPerson,
+}
+
+// the player's body only turns side to side. looking up and down lives
+// here, and gets applied to the camera
+#[derive(Component, Clone, Debug)]
+pub struct CameraRig {
+    pub pitch: f32,
+    pub mode: CameraMode,
+    // eases between standing and crouching
+    pub eye: f32,
+    // how far through a bob it is, and how much of it shows
+    bob: f32,
+    bobbing: f32,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        CameraRig {
+            pitch: 0.0,
+            mode: CameraMode::FirstPerson,
+            eye: EYE_HEIGHT,
+            bob: 0.0,
+            bobbing: 0.0,
+        }
+    }
+}
+
+impl CameraRig {
+    pub fn add_pitch(&mut self, angle: f32) {
+        self.pitch = (self.pitch + angle).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+        };
+    }
+
+    // relative to the body
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_x(self.pitch)
+    }
+
+    // where the player's eyes are and which way they're looking, in the
+    // world. the same whichever camera is in use, so aiming doesn't change
+    pub fn aim(&self, body: &Transform) -> (Vec3, Vec3) {
+        let eye = body.transform_point(Vec3::Y * self.eye);
+        (eye, body.rotation * self.rotation() * Vec3::NEG_Z)
+    }
+
+    // moves the bob along by however far the player went, fading it out
+    // when they aren't walking
+    pub fn update_bob(&mut self, distance: f32, walking: bool, dt: f32) {
+        let target = if walking { 1.0 } else { 0.0 };
+        self.bobbing += (target - self.bobbing) * (BOB_EASE * dt).min(1.0);
+        self.bob = (self.bob + distance * BOB_FREQUENCY).fract();
+    }
+
+    pub fn bob_offset(&self) -> f32 {
+        (self.bob * std::f32::consts::TAU).sin() * BOB_HEIGHT * self.bobbing
+    }
+}
+
+// a body for the player that only shows in third person
+#[derive(Component)]
+pub(crate) struct PlayerBody;
+
+pub(crate) fn update_camera(
+    time: Res<Time>,
+    settings: Res<VideoSettings>,
+    rapier: Res<RapierContext>,
+    mut players: Query<(Entity, &mut CameraRig, &PlayerMovement,
+        &GlobalTransform, &Children), With<Player>>,
+    mut cameras: Query<(&mut Transform, &mut Projection),
+        (With<Camera3d>, Without<Player>)>,
+    mut bodies: Query<&mut Visibility, With<PlayerBody>>,
+) {
+    let dt = time.delta_seconds();
+    for (player, mut rig, movement, global, children) in players.iter_mut() {
+        let eye = if movement.crouching {
+            CROUCH_EYE_HEIGHT
+        } else {
+            EYE_HEIGHT
+        };
+        if (rig.eye - eye).abs() > 0.01 {
+            rig.eye += (eye - rig.eye) * (10.0 * dt).min(1.0);
+        } else if rig.eye != eye {
+            rig.eye = eye;
+        }
+
+        // only bobs while walking along the ground
+        let speed = Vec2::new(movement.velocity.x, movement.velocity.z)
+            .length();
+        let walking = settings.head_bob && movement.grounded
+            && movement.mode == MoveMode::Walk && speed > WALK_SPEED * 0.1;
+        rig.update_bob(speed * dt, walking, dt);
+
+        let pivot = Vec3::Y * rig.eye;
+        let rotation = rig.rotation();
+        let translation = match rig.mode {
+            CameraMode::FirstPerson => pivot + Vec3::Y * rig.bob_offset(),
+            CameraMode::ThirdPerson => {
+                // pulled in so walls don't get between it and the player
+                let back = rotation * Vec3::Z;
+                let origin = global.transform_point(pivot);
+                let dir = global.affine().transform_vector3(back);
+                let filter = QueryFilter::default().exclude_collider(player);
+                let distance = rapier
+                    .cast_ray(origin, dir, ORBIT_DISTANCE, true, filter)
+                    .map(|(_, toi)| (toi - ORBIT_MARGIN).max(0.0))
+                    .unwrap_or(ORBIT_DISTANCE);
+                pivot + back * distance
+            }
+        };
+
+        let third = rig.mode == CameraMode::ThirdPerson;
+        let mut cams = cameras.iter_many_mut(children);
+        while let Some((mut trans, mut projection)) = cams.fetch_next() {
+            let transform = Transform::from_translation(translation)
+                .with_rotation(rotation);
+            if *trans != transform {
+                *trans = transform;
+            }
+
+            let fov = settings.fov_degrees.to_radians();
+            if let Projection::Perspective(p) = &*projection {
+                if p.fov != fov {
+                    *projection = Projection::Perspective(
+                        PerspectiveProjection { fov, ..p.clone() });
+                }
+            }
+        }
+
+        let mut shown = bodies.iter_many_mut(children);
+        while let Some(mut vis) = shown.fetch_next() {
+            let want = if third {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+            if *vis != want {
+                *vis = want;
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::*;
+use client_plugin::Player;
+use magic::casting::{Cooldowns, SpellSlots};
+use magic::components::{Health, MagicCaster};
+use magic::spells::Spells;
+use magic::status::VisibleStatuses;
+
+const BAR_WIDTH: f32 = 200.0;
+const BAR_HEIGHT: f32 = 12.0;
+const SLOT_SIZE: f32 = 44.0;
+const STATUS_SIZE: f32 = 24.0;
+
+const HEALTH_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
+
+#[derive(Component)]
+pub(crate) struct Hud;
+
+// the filled part of a bar
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum HudBar {
+    Health,
+    ManaA,
+    ManaB,
+}
+
+// the numbers next to a bar
+#[derive(Component)]
+pub(crate) struct HudBarLabel(HudBar);
+
+#[derive(Component)]
+pub(crate) struct ElementIcon;
+
+#[derive(Component)]
+pub(crate) struct ElementLabel;
+
+#[derive(Component)]
+pub(crate) struct SlotRow;
+
+// darkens a slot from the top down while its spell is cooling down
+#[derive(Component)]
+pub(crate) struct CooldownSweep(usize);
+
+#[derive(Component)]
+pub(crate) struct StatusRow;
+
+fn label(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(value, TextStyle {
+        font_size,
+        color: Color::WHITE,
+        ..default()
+    })
+}
+
+fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(6.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn icon(size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Px(size),
+            height: Val::Px(size),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        background_color: color.into(),
+        border_color: Color::BLACK.with_a(0.6).into(),
+        ..default()
+    }
+}
+
+fn spawn_bar(parent: &mut ChildBuilder, bar: HudBar) {
+    parent.spawn(row()).with_children(|row| {
+        row.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(BAR_HEIGHT),
+                ..default()
+            },
+            background_color: Color::BLACK.with_a(0.5).into(),
+            ..default()
+        }).with_children(|back| {
+            back.spawn((bar, NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                ..default()
+            }));
+        });
+        row.spawn((HudBarLabel(bar), label("", 12.0)));
+    });
+}
+
+pub(crate) fn setup_hud(mut commands: Commands) {
+    commands.spawn((Hud, NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(2.0),
+            width: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        ..default()
+    })).with_children(|hud| {
+        hud.spawn((StatusRow, row()));
+        hud.spawn((SlotRow, row()));
+        hud.spawn(row()).with_children(|row| {
+            row.spawn((ElementIcon, icon(SLOT_SIZE, Color::NONE)))
+                .with_children(|icon| {
+                    icon.spawn((ElementLabel, label("", 12.0)));
+                });
+            row.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(3.0),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|bars| {
+                for bar in [HudBar::Health, HudBar::ManaA, HudBar::ManaB] {
+                    spawn_bar(bars, bar);
+                }
+            });
+        });
+    });
+}
+
+// a few letters is all that fits on an icon
+fn short_name(name: &str, len: usize) -> String {
+    name.chars().take(len).collect()
+}
+
+pub(crate) fn update_hud_bars(
+    players: Query<(&MagicCaster, &Health), (With<Player>,
+        Or<(Changed<MagicCaster>, Changed<Health>)>)>,
+    mut fills: Query<(&HudBar, &mut Style, &mut BackgroundColor)>,
+    mut labels: Query<(&HudBarLabel, &mut Text), Without<ElementLabel>>,
+    mut icons: Query<&mut BackgroundColor, (With<ElementIcon>,
+        Without<HudBar>)>,
+    mut icon_labels: Query<&mut Text, With<ElementLabel>>,
+) {
+    let Ok((caster, health)) = players.get_single()
+    else {
+        return;
+    };
+
+    let values = |bar: HudBar| match bar {
+        HudBar::Health => (health.health, health.max_health, HEALTH_COLOR),
+        HudBar::ManaA => (caster.mana_a, caster.max_mana_a,
+            caster.source_color_a.color()),
+        HudBar::ManaB => (caster.mana_b, caster.max_mana_b,
+            caster.source_color_b.color()),
+    };
+
+    for (&bar, mut style, mut color) in fills.iter_mut() {
+        let (value, max, tint) = values(bar);
+        // overcharged pools just show full, the label says by how much
+        let fraction = (value as f32 / max.max(1) as f32).min(1.0);
+        style.width = Val::Percent(fraction * 100.0);
+        *color = tint.into();
+    }
+
+    for (l, mut text) in labels.iter_mut() {
+        let (value, max, _) = values(l.0);
+        text.sections[0].value = format!("{}/{}", value, max);
+    }
+
+    for mut color in icons.iter_mut() {
+        *color = caster.primary.color().into();
+    }
+    for mut text in icon_labels.iter_mut() {
+        text.sections[0].value =
+            short_name(&format!("{:?}", caster.primary), 4);
+    }
+}
+
+pub(crate) fn rebuild_spell_slots(
+    mut commands: Commands,
+    spells: Res<Spells>,
+    players: Query<(Ref<SpellSlots>, &MagicCaster), With<Player>>,
+    rows: Query<Entity, With<SlotRow>>,
+) {
+    let Ok((slots, caster)) = players.get_single()
+    else {
+        return;
+    };
+    // spells can be changed out from under the slots by a reload
+    if !slots.is_changed() && !spells.is_changed() {
+        return;
+    }
+
+    for row in rows.iter() {
+        commands.entity(row).despawn_descendants().with_children(|row| {
+            for (i, &id) in slots.0.iter().enumerate() {
+                let Some(spell) = spells.get(id)
+                else {
+                    continue;
+                };
+
+                let color = spell.resolve_element(caster).color();
+                row.spawn(icon(SLOT_SIZE, color)).with_children(|slot| {
+                    slot.spawn(label(format!("{}\n{}", i + 1,
+                        short_name(&spell.name, 5)), 11.0));
+                    slot.spawn((CooldownSweep(i), NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            top: Val::Px(0.0),
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(0.0),
+                            ..default()
+                        },
+                        background_color: Color::BLACK.with_a(0.6).into(),
+                        ..default()
+                    }));
+                });
+            }
+        });
+    }
+}
+
+pub(crate) fn update_cooldown_sweeps(
+    spells: Res<Spells>,
+    players: Query<(&SpellSlots, Option<&Cooldowns>), With<Player>>,
+    mut sweeps: Query<(&CooldownSweep, &mut Style)>,
+) {
+    let Ok((slots, cooldowns)) = players.get_single()
+    else {
+        return;
+    };
+
+    for (sweep, mut style) in sweeps.iter_mut() {
+        let fraction = slots.0.get(sweep.0)
+            .and_then(|&id| {
+                let total = spells.get(id)?.cooldown;
+                let left = cooldowns?.remaining(id);
+                (total > 0.0).then(|| (left / total).clamp(0.0, 1.0))
+            })
+            .unwrap_or(0.0);
+
+        let height = Val::Percent(fraction * 100.0);
+        if style.height != height {
+            style.height = height;
+        }
+    }
+}
+
+pub(crate) fn rebuild_status_icons(
+    mut commands: Commands,
+    players: Query<&VisibleStatuses, (With<Player>, Changed<VisibleStatuses>)>,
+    rows: Query<Entity, With<StatusRow>>,
+) {
+    let Ok(statuses) = players.get_single()
+    else {
+        return;
+    };
+
+    for row in rows.iter() {
+        commands.entity(row).despawn_descendants().with_children(|row| {
+            for kind in statuses.0.iter() {
+                row.spawn(icon(STATUS_SIZE, kind.color())).with_children(|i| {
+                    i.spawn(label(short_name(&format!("{:?}", kind), 1), 14.0));
+                });
+            }
+        });
+    }
+}
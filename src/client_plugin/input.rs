@@ -0,0 +1,390 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use bevy::input::gamepad::{GamepadAxisType, GamepadButtonType};
+use bevy::input::mouse::MouseMotion;
+use bevy::utils::HashSet;
+
+use crate::*;
+use client_plugin::settings::write_ron;
+use net::config::ConfigError;
+
+pub const DEFAULT_INPUT_PATH: &str = "input.ron";
+
+// sticks closer to the middle than this count as centred
+pub const DEFAULT_DEADZONE: f32 = 0.15;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sprint,
+    Crouch,
+    FlyUp,
+    FlyDown,
+    ToggleFly,
+    ToggleCamera,
+    CastPrimary,
+    CastSecondary,
+    CastSlot1,
+    CastSlot2,
+    CastSlot3,
+    MoveToTarget,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 18] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Sprint,
+        Action::Crouch,
+        Action::FlyUp,
+        Action::FlyDown,
+        Action::ToggleFly,
+        Action::ToggleCamera,
+        Action::CastPrimary,
+        Action::CastSecondary,
+        Action::CastSlot1,
+        Action::CastSlot2,
+        Action::CastSlot3,
+        Action::MoveToTarget,
+        Action::Pause,
+    ];
+
+    pub fn default_bindings(self) -> Vec<Binding> {
+        let key = |name: &str| Binding::Key(name.to_owned());
+        let mouse = |name: &str| Binding::Mouse(name.to_owned());
+        let pad = |name: &str| Binding::Gamepad(name.to_owned());
+        match self {
+            Action::MoveForward => vec![key("KeyW")],
+            Action::MoveBack => vec![key("KeyS")],
+            Action::MoveLeft => vec![key("KeyA")],
+            Action::MoveRight => vec![key("KeyD")],
+            Action::Jump => vec![key("Space"), pad("South")],
+            Action::Sprint => vec![key("ShiftLeft"), pad("LeftThumb")],
+            Action::Crouch => vec![key("ControlLeft"), pad("East")],
+            Action::FlyUp => vec![key("Space"), pad("South")],
+            Action::FlyDown => vec![key("ShiftLeft"), pad("East")],
+            Action::ToggleFly => vec![key("KeyV"), pad("Select")],
+            Action::ToggleCamera => vec![key("F5"), pad("RightThumb")],
+            Action::CastPrimary => vec![mouse("Left"), pad("RightTrigger2")],
+            Action::CastSecondary => vec![mouse("Right"), pad("LeftTrigger2")],
+            Action::CastSlot1 => vec![key("Digit1")],
+            Action::CastSlot2 => vec![key("Digit2")],
+            Action::CastSlot3 => vec![key("Digit3"), pad("DPadUp")],
+            Action::MoveToTarget => vec![mouse("Middle"), pad("West")],
+            Action::Pause => vec![key("Escape"), pad("Start")],
+        }
+    }
+
+    // the spell slot the action casts. the mouse buttons cast the first two
+    // and the number keys cast whichever slot the hud numbers them as
+    pub fn cast_slot(self) -> Option<usize> {
+        match self {
+            Action::CastPrimary | Action::CastSlot1 => Some(0),
+            Action::CastSecondary | Action::CastSlot2 => Some(1),
+            Action::CastSlot3 => Some(2),
+            _ => None,
+        }
+    }
+}
+
+// inputs are named after bevy's variants, e.g. Key("KeyW") or
+// Gamepad("South"), since bevy's types aren't serializable here
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(String),
+    Mouse(String),
+    Gamepad(String),
+}
+
+// the bevy inputs a binding names
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+macro_rules! named {
+    ($ty:ident: $($name:ident),* $(,)?) => {
+        &[$((stringify!($name), $ty::$name)),*]
+    };
+}
+
+const KEYS: &[(&str, KeyCode)] = named!(KeyCode:
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL,
+    KeyM, KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX,
+    KeyY, KeyZ,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8,
+    Digit9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+    Space, Enter, Escape, Tab, Backspace, CapsLock, Backquote,
+    ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+    Comma, Period, Slash, Semicolon, Quote, BracketLeft, BracketRight,
+    Minus, Equal, Backslash,
+);
+
+const MOUSE_BUTTONS: &[(&str, MouseButton)] = named!(MouseButton:
+    Left, Right, Middle, Back, Forward,
+);
+
+const GAMEPAD_BUTTONS: &[(&str, GamepadButtonType)] = named!(
+    GamepadButtonType:
+    South, East, North, West, C, Z,
+    LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+    Select, Start, Mode, LeftThumb, RightThumb,
+    DPadUp, DPadDown, DPadLeft, DPadRight,
+);
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(n, _)| *n == name).map(|&(_, t)| t)
+}
+
+fn name_of<T: PartialEq>(table: &[(&str, T)], value: T) -> Option<String> {
+    table.iter().find(|(_, t)| *t == value).map(|(n, _)| n.to_string())
+}
+
+impl Binding {
+    // the binding for an input, if it's one that can be named
+    pub fn from_input(input: Input) -> Option<Binding> {
+        match input {
+            Input::Key(key) => name_of(KEYS, key).map(Binding::Key),
+            Input::Mouse(button) =>
+                name_of(MOUSE_BUTTONS, button).map(Binding::Mouse),
+            Input::Gamepad(button) =>
+                name_of(GAMEPAD_BUTTONS, button).map(Binding::Gamepad),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(name) => name.clone(),
+            Binding::Mouse(name) => format!("Mouse {}", name),
+            Binding::Gamepad(name) => format!("Pad {}", name),
+        }
+    }
+
+    pub fn input(&self) -> Option<Input> {
+        match self {
+            Binding::Key(name) => lookup(KEYS, name).map(Input::Key),
+            Binding::Mouse(name) =>
+                lookup(MOUSE_BUTTONS, name).map(Input::Mouse),
+            Binding::Gamepad(name) =>
+                lookup(GAMEPAD_BUTTONS, name).map(Input::Gamepad),
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct InputSettings {
+    // actions left out keep their default bindings
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    // radians turned per pixel the mouse moves
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    // radians per second with the right stick all the way over
+    pub gamepad_look_speed: f32,
+    pub gamepad_deadzone: f32,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            bindings: Action::ALL.iter()
+                .map(|&a| (a, a.default_bindings()))
+                .collect(),
+            mouse_sensitivity: 1.0 / 200.0,
+            invert_y: false,
+            gamepad_look_speed: 3.0,
+            gamepad_deadzone: DEFAULT_DEADZONE,
+        }
+    }
+}
+
+impl InputSettings {
+    pub fn load(path: &Path) -> Result<InputSettings, ConfigError> {
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(InputSettings::default());
+            }
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+
+        let mut settings: InputSettings = ron::from_str(&src)
+            .map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        for action in Action::ALL {
+            settings.bindings.entry(action)
+                .or_insert_with(|| action.default_bindings());
+        }
+
+        let known = settings.bindings.values().flatten()
+            .all(|b| b.input().is_some());
+        if !known {
+            return Err(ConfigError::Invalid("unknown input in bindings"));
+        }
+        if !(settings.gamepad_deadzone >= 0.0
+            && settings.gamepad_deadzone < 1.0)
+        {
+            return Err(ConfigError::Invalid(
+                "gamepad deadzone must be between 0 and 1"));
+        }
+        // nan would end up in the player's rotation
+        let speeds = [settings.mouse_sensitivity, settings.gamepad_look_speed];
+        if !speeds.iter().all(|s| s.is_finite() && *s > 0.0) {
+            return Err(ConfigError::Invalid(
+                "mouse sensitivity and look speed must be positive"));
+        }
+
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        write_ron(self, path)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // swaps the action's keyboard and mouse bindings for `binding`,
+    // leaving any gamepad buttons alone
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|b| matches!(b, Binding::Gamepad(_)));
+        bindings.insert(0, binding);
+    }
+}
+
+// what the player is asking for this frame, whatever it's bound to
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // from the left stick, x to the right and y forwards
+    pub stick: Vec2,
+    // how far to turn this frame in radians, x to the right and y up
+    pub look: Vec2,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    // every action with an input held down, given a way to check inputs
+    pub fn update(
+        &mut self,
+        settings: &InputSettings,
+        mut held: impl FnMut(Input) -> bool,
+    ) {
+        let pressed: HashSet<_> = settings.bindings.iter()
+            .filter(|(_, bindings)| bindings.iter()
+                .filter_map(Binding::input)
+                .any(&mut held))
+            .map(|(&action, _)| action)
+            .collect();
+        self.just_pressed = pressed.difference(&self.pressed)
+            .copied()
+            .collect();
+        self.pressed = pressed;
+    }
+
+    // where the movement actions and left stick point, at most 1 long
+    pub fn movement(&self) -> Vec2 {
+        let axis = |pos, neg| {
+            self.pressed(pos) as i32 as f32 - self.pressed(neg) as i32 as f32
+        };
+        let keys = Vec2::new(
+            axis(Action::MoveRight, Action::MoveLeft),
+            axis(Action::MoveForward, Action::MoveBack),
+        );
+        (keys + self.stick).clamp_length_max(1.0)
+    }
+}
+
+fn deadzone(stick: Vec2, zone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= zone {
+        return Vec2::ZERO;
+    }
+    // rescaled so it still goes smoothly from nothing to full tilt
+    stick / length * ((length - zone) / (1.0 - zone)).min(1.0)
+}
+
+// every connected gamepad's stick added up
+fn read_stick(
+    gamepads: &Gamepads,
+    axes: &Axis<GamepadAxis>,
+    (x, y): (GamepadAxisType, GamepadAxisType),
+    zone: f32,
+) -> Vec2 {
+    let sum: Vec2 = gamepads.iter()
+        .map(|g| Vec2::new(
+            axes.get(GamepadAxis::new(g, x)).unwrap_or_default(),
+            axes.get(GamepadAxis::new(g, y)).unwrap_or_default(),
+        ))
+        .sum();
+    deadzone(sum.clamp_length_max(1.0), zone)
+}
+
+pub(crate) fn update_actions(
+    settings: Res<InputSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<ButtonInput<GamepadButton>>,
+    pad_axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<ActionState>,
+) {
+    actions.update(&settings, |input| match input {
+        Input::Key(key) => keys.pressed(key),
+        Input::Mouse(button) => mouse.pressed(button),
+        Input::Gamepad(button) => gamepads.iter()
+            .any(|g| pad_buttons.pressed(GamepadButton::new(g, button))),
+    });
+
+    actions.stick = read_stick(&gamepads, &pad_axes,
+        (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+        settings.gamepad_deadzone);
+}
+
+pub(crate) fn update_look(
+    time: Res<Time>,
+    settings: Res<InputSettings>,
+    mut motion: EventReader<MouseMotion>,
+    gamepads: Res<Gamepads>,
+    pad_axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<ActionState>,
+) {
+    // the mouse moves down the screen as y goes up
+    let mouse: Vec2 = motion.read()
+        .map(|m| m.delta * Vec2::new(1.0, -1.0))
+        .sum();
+    let pad = read_stick(&gamepads, &pad_axes,
+        (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        settings.gamepad_deadzone);
+
+    let mut look = mouse * settings.mouse_sensitivity
+        + pad * settings.gamepad_look_speed * time.delta_seconds();
+    if settings.invert_y {
+        look.y = -look.y;
+    }
+    actions.look = look;
+}
@@ -0,0 +1,404 @@
+use bevy::app::AppExit;
+use bevy::window::CursorGrabMode;
+use client::{ClientCommands, NetworkingState};
+use server::ServerCommands;
+
+use crate::*;
+use client_plugin::Paused;
+use client_plugin::input::{Action, ActionState, Binding, Input};
+use client_plugin::input::InputSettings;
+use client_plugin::settings::{SettingsPaths, VideoSettings};
+use client_plugin::settings::{MAX_FOV, MAX_RENDER_DISTANCE, MIN_FOV};
+use client_plugin::settings::MIN_RENDER_DISTANCE;
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.2);
+const HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.35);
+const PRESSED_COLOR: Color = Color::rgb(0.35, 0.35, 0.5);
+
+// each step of the sensitivity buttons scales it by this much
+const SENSITIVITY_STEP: f32 = 1.25;
+
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Menu {
+    #[default]
+    Closed,
+    Pause,
+    Settings,
+    // waiting for a key or mouse button to bind the action to
+    Rebinding(Action),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Setting {
+    RenderDistance,
+    Fov,
+    Sensitivity,
+    InvertY,
+    HeadBob,
+    ShowFps,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 6] = [
+        Setting::RenderDistance,
+        Setting::Fov,
+        Setting::Sensitivity,
+        Setting::InvertY,
+        Setting::HeadBob,
+        Setting::ShowFps,
+    ];
+
+    pub fn is_toggle(self) -> bool {
+        matches!(self, Setting::InvertY | Setting::HeadBob | Setting::ShowFps)
+    }
+
+    pub fn label(self, video: &VideoSettings, input: &InputSettings) -> String {
+        let on = |b: bool| if b { "on" } else { "off" };
+        match self {
+            Setting::RenderDistance =>
+                format!("Render distance: {} chunks", video.render_distance),
+            Setting::Fov => format!("Field of view: {:.0}", video.fov_degrees),
+            Setting::Sensitivity => {
+                let default = InputSettings::default().mouse_sensitivity;
+                format!("Mouse sensitivity: {:.0}%",
+                    input.mouse_sensitivity / default * 100.0)
+            }
+            Setting::InvertY => format!("Invert Y: {}", on(input.invert_y)),
+            Setting::HeadBob => format!("Head bob: {}", on(video.head_bob)),
+            Setting::ShowFps => format!("FPS counter: {}", on(video.show_fps)),
+        }
+    }
+
+    // moves the setting up or down by `steps`, or flips it if it's a toggle
+    pub fn adjust(
+        self,
+        steps: i32,
+        video: &mut VideoSettings,
+        input: &mut InputSettings,
+    ) {
+        match self {
+            Setting::RenderDistance => {
+                video.render_distance = (video.render_distance + steps)
+                    .clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+            }
+            Setting::Fov => {
+                video.fov_degrees = (video.fov_degrees + 5.0 * steps as f32)
+                    .clamp(MIN_FOV, MAX_FOV);
+            }
+            Setting::Sensitivity => {
+                let default = InputSettings::default().mouse_sensitivity;
+                input.mouse_sensitivity = (input.mouse_sensitivity
+                    * SENSITIVITY_STEP.powi(steps))
+                    .clamp(default / 8.0, default * 8.0);
+            }
+            Setting::InvertY => input.invert_y ^= true,
+            Setting::HeadBob => video.head_bob ^= true,
+            Setting::ShowFps => video.show_fps ^= true,
+        }
+    }
+}
+
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MenuButton {
+    Resume,
+    OpenSettings,
+    Disconnect,
+    Quit,
+    Back,
+    Adjust(Setting, i32),
+    Rebind(Action),
+    ResetBindings,
+}
+
+#[derive(Component)]
+pub(crate) struct MenuRoot;
+
+fn save_settings(
+    paths: &SettingsPaths,
+    video: &VideoSettings,
+    input: &InputSettings,
+) {
+    if let Err(e) = video.save(&paths.video) {
+        error!("{}", e);
+    }
+    if let Err(e) = input.save(&paths.input) {
+        error!("{}", e);
+    }
+}
+
+// the pause key backs out a screen at a time, and rebinding takes the next
+// key or mouse button pressed
+pub(crate) fn handle_menu_keys(
+    mut menu: ResMut<Menu>,
+    actions: Res<ActionState>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut input: ResMut<InputSettings>,
+    video: Res<VideoSettings>,
+    paths: Res<SettingsPaths>,
+) {
+    if let Menu::Rebinding(action) = *menu {
+        if keys.just_pressed(KeyCode::Escape) {
+            *menu = Menu::Settings;
+            return;
+        }
+
+        let pressed = keys.get_just_pressed().map(|&k| Input::Key(k))
+            .chain(mouse.get_just_pressed().map(|&b| Input::Mouse(b)))
+            .find_map(Binding::from_input);
+        if let Some(binding) = pressed {
+            input.rebind(action, binding);
+            *menu = Menu::Settings;
+        }
+        return;
+    }
+
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    *menu = match *menu {
+        Menu::Closed => Menu::Pause,
+        Menu::Pause => Menu::Closed,
+        Menu::Settings | Menu::Rebinding(_) => {
+            save_settings(&paths, &video, &input);
+            Menu::Pause
+        }
+    };
+}
+
+pub(crate) fn press_menu_buttons(
+    mut commands: Commands,
+    mut menu: ResMut<Menu>,
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut video: ResMut<VideoSettings>,
+    mut input: ResMut<InputSettings>,
+    paths: Res<SettingsPaths>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, &button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            MenuButton::Resume => *menu = Menu::Closed,
+            MenuButton::OpenSettings => *menu = Menu::Settings,
+            MenuButton::Disconnect => {
+                commands.disconnect_client();
+                commands.stop_server();
+                *menu = Menu::Closed;
+            }
+            MenuButton::Quit => {
+                exit.send(AppExit);
+            }
+            MenuButton::Back => {
+                save_settings(&paths, &video, &input);
+                *menu = Menu::Pause;
+            }
+            MenuButton::Adjust(setting, steps) =>
+                setting.adjust(steps, &mut video, &mut input),
+            MenuButton::Rebind(action) => *menu = Menu::Rebinding(action),
+            MenuButton::ResetBindings => {
+                input.bindings = InputSettings::default().bindings;
+            }
+        }
+    }
+}
+
+// everything else stops while the menu's open, and the cursor is let go
+pub(crate) fn sync_pause(
+    menu: Res<Menu>,
+    mut paused: ResMut<Paused>,
+    mut windows: Query<&mut Window>,
+    mut was_open: Local<bool>,
+) {
+    let open = *menu != Menu::Closed;
+    // the game stays paused for the frame the menu closes on, or the click
+    // on resume would also count as a click in the game
+    let pause = open || *was_open;
+    if paused.0 != pause {
+        paused.0 = pause;
+    }
+    if *was_open == open {
+        return;
+    }
+    *was_open = open;
+
+    for mut window in windows.iter_mut() {
+        window.cursor.visible = open;
+        window.cursor.grab_mode = if open {
+            CursorGrabMode::None
+        } else {
+            CursorGrabMode::Locked
+        };
+    }
+}
+
+fn label(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(value, TextStyle {
+        font_size,
+        color: Color::WHITE,
+        ..default()
+    })
+}
+
+fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: MenuButton,
+    text: impl Into<String>,
+    width: f32,
+) {
+    parent.spawn((button, ButtonBundle {
+        style: Style {
+            width: Val::Px(width),
+            padding: UiRect::all(Val::Px(6.0)),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        background_color: BUTTON_COLOR.into(),
+        ..default()
+    })).with_children(|b| {
+        b.spawn(label(text, 18.0));
+    });
+}
+
+fn spawn_pause(parent: &mut ChildBuilder, connected: bool) {
+    parent.spawn(label("Paused", 32.0));
+    spawn_button(parent, MenuButton::Resume, "Resume", 220.0);
+    spawn_button(parent, MenuButton::OpenSettings, "Settings", 220.0);
+    if connected {
+        spawn_button(parent, MenuButton::Disconnect, "Disconnect", 220.0);
+    }
+    spawn_button(parent, MenuButton::Quit, "Quit", 220.0);
+}
+
+fn spawn_settings(
+    parent: &mut ChildBuilder,
+    video: &VideoSettings,
+    input: &InputSettings,
+    rebinding: Option<Action>,
+) {
+    parent.spawn(label("Settings", 32.0));
+    for setting in Setting::ALL {
+        parent.spawn(row()).with_children(|row| {
+            let text = setting.label(video, input);
+            if setting.is_toggle() {
+                spawn_button(row, MenuButton::Adjust(setting, 1), text, 320.0);
+                return;
+            }
+            spawn_button(row, MenuButton::Adjust(setting, -1), "-", 40.0);
+            row.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(224.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            }).with_children(|middle| {
+                middle.spawn(label(text, 18.0));
+            });
+            spawn_button(row, MenuButton::Adjust(setting, 1), "+", 40.0);
+        });
+    }
+
+    parent.spawn(label("Key bindings", 24.0));
+    parent.spawn(NodeBundle {
+        style: Style {
+            display: Display::Grid,
+            grid_template_columns: RepeatedGridTrack::auto(4),
+            column_gap: Val::Px(8.0),
+            row_gap: Val::Px(4.0),
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|grid| {
+        for action in Action::ALL {
+            grid.spawn(label(format!("{:?}", action), 16.0));
+            let text = if rebinding == Some(action) {
+                "press a key...".to_owned()
+            } else {
+                input.bindings(action).iter()
+                    .map(Binding::label)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            spawn_button(grid, MenuButton::Rebind(action), text, 240.0);
+        }
+    });
+
+    parent.spawn(row()).with_children(|row| {
+        spawn_button(row, MenuButton::ResetBindings, "Reset bindings", 200.0);
+        spawn_button(row, MenuButton::Back, "Back", 200.0);
+    });
+}
+
+pub(crate) fn rebuild_menu(
+    mut commands: Commands,
+    menu: Res<Menu>,
+    video: Res<VideoSettings>,
+    input: Res<InputSettings>,
+    net: Option<Res<State<NetworkingState>>>,
+    roots: Query<Entity, With<MenuRoot>>,
+) {
+    if !menu.is_changed() && !video.is_changed() && !input.is_changed() {
+        return;
+    }
+
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+    if *menu == Menu::Closed {
+        return;
+    }
+
+    let connected = net.is_some_and(|s| {
+        *s.get() != NetworkingState::Disconnected
+    });
+    commands.spawn((MenuRoot, NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        background_color: Color::BLACK.with_a(0.6).into(),
+        z_index: ZIndex::Global(i32::MAX - 1),
+        ..default()
+    })).with_children(|root| match *menu {
+        Menu::Closed => (),
+        Menu::Pause => spawn_pause(root, connected),
+        Menu::Settings => spawn_settings(root, &video, &input, None),
+        Menu::Rebinding(action) =>
+            spawn_settings(root, &video, &input, Some(action)),
+    });
+}
+
+pub(crate) fn highlight_buttons(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &MenuButton),
+        Changed<Interaction>>,
+) {
+    for (interaction, mut color, _) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Pressed => PRESSED_COLOR,
+            Interaction::Hovered => HOVER_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        }.into();
+    }
+}
@@ -1,15 +1,50 @@
-use bevy::{window::CursorGrabMode, input::mouse::MouseMotion};
+use bevy::window::CursorGrabMode;
 use noise::{NoiseFn, Perlin};
 
-use crate::voxel::{VoxelRes, CHUNK_DIM, CHUNK_SIZE_I32, VoxelConfigEntry, VOXEL_SIZE};
+use crate::voxel::{VoxelRes, CHUNK_DIM, CHUNK_SIZE_I32, VOXEL_SIZE};
 use crate::voxel::components::ChunkLoader;
+use crate::voxel::debris::VoxelDebris;
+use crate::voxel::edit::{ProtectedRegion, ProtectedRegions};
+use crate::voxel::path::{Agent, PathCache, PathFollower, MAX_FALL};
+use crate::voxel::path::STEP_HEIGHT;
+use crate::magic::ManaColor;
+use crate::magic::casting::{CastSpell, SpellSlots, SpellTarget};
+use crate::magic::components::{Health, MagicCaster};
+use crate::magic::damage::{Dead, Respawn};
+use crate::magic::mana::ManaRegen;
+use crate::magic::projectile::Projectile;
+use crate::magic::spells::{Spells, Targeting};
+use crate::magic::status::{StatusEffects, VisibleStatuses};
+use crate::npc::Npc;
+use self::camera::{CameraRig, PlayerBody};
+use self::input::{Action, ActionState};
+use self::movement::{MoveInput, MoveMode, PlayerMovement, EYE_HEIGHT};
+use self::settings::VideoSettings;
 use crate::*;
 
 use self::voxel::{ChunkGenerator, VoxelId, Voxels, CHUNK_SIZE_CB};
 
+pub mod camera;
+pub mod hud;
+pub mod input;
+pub mod menu;
+pub mod movement;
+pub mod settings;
+
 #[derive(Component)]
 pub struct Player;
 
+// from the bottom of the capsule to the top
+pub const PLAYER_HEIGHT: f32 = 4.2;
+pub const CROUCH_HEIGHT: f32 = 2.6;
+pub const PLAYER_RADIUS: f32 = 0.4;
+// how far the middle of the player drops when crouching, with their feet
+// staying put
+pub const CROUCH_DROP: f32 = (PLAYER_HEIGHT - CROUCH_HEIGHT) / 2.0;
+
+// how far click to move will look for somewhere to go
+pub const MOVE_RANGE: f32 = 100.0;
+
 #[derive(Resource, Default)]
 pub struct Paused(bool);
 
@@ -17,6 +52,7 @@ pub(crate) fn setup_scene(
     mut commands: Commands,
     mut windows: Query<&mut Window>,
     voxels: Res<VoxelRes>,
+    mut protected: ResMut<ProtectedRegions>,
 ) {
     let mut window = windows.single_mut();
     window.cursor.visible = false;
@@ -27,12 +63,14 @@ pub(crate) fn setup_scene(
         return;
     };
 
-    voxels.add_voxel("solid", VoxelConfigEntry {
-        debug_name: "solid".to_owned(),
-        render: true,
-        solid: true,
-        color: Color::rgb_u8(255, 255, 0),
+    voxel::register_default_voxels(&mut voxels);
+
+    // keep spells from burying or undermining the spawn point
+    protected.0.push(ProtectedRegion {
+        min: IVec3::new(4, -16, 4),
+        max: IVec3::new(12, 32, 12),
     });
+
     commands.init_resource::<Paused>();
     commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
@@ -46,114 +84,435 @@ pub(crate) fn setup_scene(
     });
 }
 
-pub(crate) fn setup_player(mut commands: Commands) {
+pub(crate) fn setup_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    spells: Res<Spells>,
+    video: Res<VideoSettings>,
+    fixed: Res<Time<Fixed>>,
+) {
+    let slots = ["bolt", "mend", "spark"].iter()
+        .filter_map(|name| spells.id_from_name(name))
+        .collect();
+    let caster = MagicCaster::new(ManaColor::Yellow, ManaColor::Blue, 100, 100);
+    let primary = caster.primary;
+    let spawn = Vec3::new(4.0, 8.0, 4.0);
+
     commands.spawn((TransformBundle {
-            local: Transform::from_xyz(4.0, 8.0, 4.0)
+            local: Transform::from_translation(spawn)
                 .looking_to(Vec3::NEG_Z, Vec3::Y),
             ..Default::default()
-        }, Player, ChunkLoader {
-            x_radius: 10,
+        }, VisibilityBundle::default(), Player, PlayerMovement::default(),
+        CameraRig::default(), ChunkLoader {
+            x_radius: video.render_distance,
             y_radius: 0,
-            z_radius: 10
+            z_radius: video.render_distance,
         }, RigidBody::KinematicPositionBased,
-        Collider::capsule_y(1.7, 0.4),
+        Collider::capsule_y(PLAYER_HEIGHT / 2.0 - PLAYER_RADIUS, PLAYER_RADIUS),
         KinematicCharacterController {
             offset: CharacterLength::Absolute(0.01),
             autostep: Some(CharacterAutostep {
-                max_height: CharacterLength::Absolute(0.51),
+                max_height: CharacterLength::Absolute(STEP_HEIGHT),
                 min_width: CharacterLength::Absolute(0.49),
                 include_dynamic_bodies: false,
             }),
-            snap_to_ground: Some(CharacterLength::Absolute(0.51)),
+            snap_to_ground: Some(CharacterLength::Absolute(STEP_HEIGHT)),
             apply_impulse_to_dynamic_bodies: true,
             ..default()
-        }))
+        }, caster, Health {
+            health: 100,
+            max_health: 100,
+            typed: primary,
+        }, Respawn {
+            point: spawn,
+            delay: 3.0,
+        }, ManaRegen::per_second(2.0, 2.0, fixed.timestep().as_secs_f32()),
+        SpellSlots(slots)))
     .with_children(|cs| {
         cs.spawn(Camera3dBundle {
-            transform: Transform::from_xyz(0.0, 1.6, 0.0)
+            transform: Transform::from_xyz(0.0, EYE_HEIGHT, 0.0)
                 .looking_to(Vec3::NEG_Z, Vec3::Y),
             ..Default::default()
         });
+        cs.spawn((PbrBundle {
+            mesh: meshes.add(Capsule3d::new(PLAYER_RADIUS,
+                PLAYER_HEIGHT - PLAYER_RADIUS * 2.0)),
+            material: materials.add(primary.color()),
+            visibility: Visibility::Hidden,
+            ..default()
+        }, PlayerBody));
     });
 }
 
 pub(crate) fn handle_mouse(
     paused: Res<Paused>,
-    mut cursor_events: EventReader<MouseMotion>,
-    mut q: Query<&mut Transform, With<Player>>,
+    actions: Res<ActionState>,
+    mut q: Query<(&mut Transform, &mut CameraRig), With<Player>>,
 ) {
     if paused.0 {
         return;
     }
 
-    let mut trans = q.single_mut();
-    for event in cursor_events.read() {
-        trans.rotate_axis(Vec3::Y, -event.delta.x / 200.0);
-        let x = trans.local_x().into();
-        trans.rotate_axis(x, -event.delta.y / 200.0);
+    // the body only turns, the camera looks up and down
+    let (mut trans, mut rig) = q.single_mut();
+    trans.rotate_y(-actions.look.x);
+    rig.add_pitch(actions.look.y);
+
+    if actions.just_pressed(Action::ToggleCamera) {
+        rig.toggle_mode();
     }
 }
 
 pub(crate) fn handle_input(
-    mut paused: ResMut<Paused>,
-    mut windows: Query<&mut Window>,
-    mut q: Query<(&mut KinematicCharacterController, &Transform),
-        With<Player>>,
-    keys: Res<ButtonInput<KeyCode>>,
+    mut commands: Commands,
+    paused: Res<Paused>,
+    actions: Res<ActionState>,
+    rapier: Res<RapierContext>,
+    mut q: Query<(Entity, &mut KinematicCharacterController,
+        Option<&KinematicCharacterControllerOutput>, &mut PlayerMovement,
+        &Transform, Has<Dead>, Option<&StatusEffects>,
+        Option<&mut PathFollower>), With<Player>>,
     time: Res<Time>,
 ) {
-    let mut window = windows.single_mut();
+    if paused.0 {
+        return;
+    }
+
+    let (player, mut cont, output, mut movement, trans, dead, statuses,
+        follower) = q.single_mut();
+    if dead {
+        return;
+    }
 
-    if keys.just_pressed(KeyCode::Escape) {
-        paused.0 ^= true;
-        window.cursor.visible = paused.0;
-        window.cursor.grab_mode = if paused.0 {
-            CursorGrabMode::None
+    if actions.just_pressed(Action::ToggleFly) {
+        movement.toggle_mode();
+    }
+
+    let mut forward: Vec3 = trans.forward().into();
+    forward.y = 0.0;
+    let left: Vec3 = trans.left().into();
+    let flying = movement.mode == MoveMode::Fly;
+
+    let movement_axes = actions.movement();
+    let mut wish = forward.normalize_or_zero() * movement_axes.y
+        - left * movement_axes.x;
+    if flying && actions.pressed(Action::FlyUp) {
+        wish += Vec3::Y;
+    }
+    if flying && actions.pressed(Action::FlyDown) {
+        wish += Vec3::NEG_Y;
+    }
+
+    // moving by hand cancels click to move
+    if let Some(mut follower) = follower {
+        let dir = if wish == Vec3::ZERO {
+            follower.steer(feet(trans, &movement))
         } else {
-            CursorGrabMode::Locked
+            None
         };
+        match dir {
+            Some(dir) if flying => wish = dir,
+            Some(dir) => {
+                wish = Vec3::new(dir.x, 0.0, dir.z).normalize_or_zero();
+            }
+            None => {
+                commands.entity(player).remove::<PathFollower>();
+            }
+        }
+    }
+
+    // where a standing player would be, nudged up off the floor
+    let headroom = !movement.crouching || {
+        let standing = Collider::capsule_y(
+            PLAYER_HEIGHT / 2.0 - PLAYER_RADIUS, PLAYER_RADIUS);
+        let centre = trans.translation + Vec3::Y * (CROUCH_DROP + 0.05);
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_collider(player);
+        rapier.intersection_with_shape(
+            centre, trans.rotation, &standing, filter).is_none()
+    };
+
+    let input = MoveInput {
+        wish,
+        jump: !flying && actions.pressed(Action::Jump),
+        sprint: !flying && actions.pressed(Action::Sprint),
+        crouch: !flying && actions.pressed(Action::Crouch),
+        speed: statuses.map(|s| s.speed_multiplier()).unwrap_or(1.0),
+        headroom,
+    };
+    // the normal is on the player's side, so it points up into a ceiling
+    let bumped = output.is_some_and(|o| o.collisions.iter()
+        .any(|c| c.toi.normal1.y > 0.5));
+    if bumped {
+        movement.hit_ceiling();
+    }
+    let grounded = output.is_some_and(|o| o.grounded);
+    let step = movement.step(input, grounded, time.delta_seconds());
+    cont.translation = Some(step);
+}
+
+fn player_height(movement: &PlayerMovement) -> f32 {
+    if movement.crouching {
+        CROUCH_HEIGHT
+    } else {
+        PLAYER_HEIGHT
     }
+}
+
+// the bottom of the player's capsule
+fn feet(trans: &Transform, movement: &PlayerMovement) -> Vec3 {
+    trans.translation - Vec3::Y * player_height(movement) / 2.0
+}
+
+// the collider shrinks while crouched, keeping the player's feet where they
+// are. the camera is moved back up by as much, so it eases down instead of
+// jumping
+pub(crate) fn fit_player_to_stance(
+    mut players: Query<(&PlayerMovement, &mut Collider, &mut Transform,
+        &mut CameraRig, &Children), With<Player>>,
+    mut bodies: Query<&mut Transform, (With<PlayerBody>, Without<Player>)>,
+) {
+    for (movement, mut collider, mut trans, mut rig, children) in
+        players.iter_mut()
+    {
+        let height = player_height(movement);
+        let half = height / 2.0 - PLAYER_RADIUS;
+        let fits = collider.as_capsule()
+            .is_some_and(|c| (c.half_height() - half).abs() < 1e-3);
+        if fits {
+            continue;
+        }
+
+        let drop = if movement.crouching { CROUCH_DROP } else { -CROUCH_DROP };
+        *collider = Collider::capsule_y(half, PLAYER_RADIUS);
+        trans.translation.y -= drop;
+        rig.eye += drop;
 
+        let mut shown = bodies.iter_many_mut(children);
+        while let Some(mut body) = shown.fetch_next() {
+            body.scale.y = height / PLAYER_HEIGHT;
+        }
+    }
+}
+
+pub(crate) fn handle_casting(
+    paused: Res<Paused>,
+    actions: Res<ActionState>,
+    spells: Res<Spells>,
+    rapier: Res<RapierContext>,
+    q: Query<(Entity, &Transform, &CameraRig, &SpellSlots), With<Player>>,
+    mut casts: EventWriter<CastSpell>,
+) {
     if paused.0 {
         return;
     }
 
-    let (mut cont, trans) = q.single_mut();
-    let mut forward: Vec3 = trans.forward().into();
-    forward.y = 0.0;
-    let left: Vec3 = trans.left().into();
-    const SPEED: f32 = 15.0;
+    let (player, trans, rig, slots) = q.single();
+    let Some(slot) = Action::ALL.into_iter()
+        .filter(|&action| actions.just_pressed(action))
+        .find_map(Action::cast_slot)
+    else {
+        return;
+    };
+
+    let Some(&spell_id) = slots.0.get(slot)
+    else {
+        return;
+    };
+    let Some(spell) = spells.get(spell_id)
+    else {
+        return;
+    };
+
+    let (eye, dir) = rig.aim(trans);
+    let filter = QueryFilter::default().exclude_collider(player);
+
+    let target = match spell.targeting {
+        Targeting::Caster => SpellTarget::Caster,
+        Targeting::Direction => SpellTarget::Direction(dir),
+        Targeting::Entity { range } => {
+            let Some((hit, _)) =
+                rapier.cast_ray(eye, dir, range, true, filter)
+            else {
+                return;
+            };
+            SpellTarget::Entity(hit)
+        }
+        Targeting::Point { range } => {
+            let Some((_, toi)) =
+                rapier.cast_ray(eye, dir, range, true, filter)
+            else {
+                return;
+            };
+            SpellTarget::Point(eye + dir * toi)
+        }
+    };
+
+    casts.send(CastSpell {
+        caster: player,
+        spell: spell_id,
+        target,
+    });
+}
 
-    let mut add = Vec3::ZERO;
+pub(crate) fn player_agent() -> Agent {
+    Agent::new(PLAYER_HEIGHT, STEP_HEIGHT, MAX_FALL)
+}
 
-    if keys.pressed(KeyCode::KeyW) {
-        add += forward;
+// middle click walks the player over to wherever they're looking
+pub(crate) fn click_to_move(
+    mut commands: Commands,
+    paused: Res<Paused>,
+    actions: Res<ActionState>,
+    rapier: Res<RapierContext>,
+    voxels: Res<VoxelRes>,
+    mut paths: ResMut<PathCache>,
+    q: Query<(Entity, &Transform, &CameraRig, &PlayerMovement),
+        (With<Player>, Without<Dead>)>,
+) {
+    if paused.0 || !actions.just_pressed(Action::MoveToTarget) {
+        return;
     }
-    if keys.pressed(KeyCode::KeyS) {
-        add += -forward;
+    let Ok((player, trans, rig, movement)) = q.get_single()
+    else {
+        return;
+    };
+
+    let (eye, dir) = rig.aim(trans);
+    let filter = QueryFilter::default().exclude_collider(player);
+    let Some((_, hit)) = rapier.cast_ray_and_get_normal(
+        eye, dir, MOVE_RANGE, true, filter)
+    else {
+        return;
+    };
+    let Ok(voxels) = voxels.try_read()
+    else {
+        return;
+    };
+
+    // just off the surface that was clicked on, then down onto the ground
+    let agent = player_agent();
+    let start = agent.standing_cell(&*voxels, feet(trans, movement));
+    let goal = agent.standing_cell(&*voxels,
+        hit.point + hit.normal * VOXEL_SIZE / 2.0);
+    let (Some(start), Some(goal)) = (start, goal)
+    else {
+        return;
+    };
+
+    if let Some(path) = paths.find(&voxels, agent, start, goal) {
+        commands.entity(player).insert(PathFollower::new(path));
     }
-    if keys.pressed(KeyCode::KeyA) {
-        add += left;
+}
+
+pub(crate) fn attach_projectile_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    projectiles: Query<(Entity, &Projectile), Added<Projectile>>,
+) {
+    for (entity, projectile) in projectiles.iter() {
+        let color = projectile.element.color();
+        commands.entity(entity).insert((
+            meshes.add(Sphere::new(projectile.radius)),
+            materials.add(StandardMaterial {
+                base_color: color,
+                emissive: color,
+                ..default()
+            }),
+            VisibilityBundle::default(),
+        ));
     }
-    if keys.pressed(KeyCode::KeyD) {
-        add += -left;
+}
+
+pub(crate) fn attach_debris_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    voxels: Res<VoxelRes>,
+    debris: Query<(Entity, &VoxelDebris), Added<VoxelDebris>>,
+) {
+    if debris.is_empty() {
+        return;
     }
 
-    add = add.normalize_or_zero() * SPEED * time.delta_seconds();
+    let Ok(voxels) = voxels.read()
+    else {
+        return;
+    };
 
-    if keys.pressed(KeyCode::Space) {
-        add += Vec3::Y * SPEED * time.delta_seconds();
+    for (entity, debris) in debris.iter() {
+        commands.entity(entity).insert((
+            meshes.add(debris.mesh(&voxels)),
+            materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                ..default()
+            }),
+            VisibilityBundle::default(),
+        ));
     }
+}
 
-    if keys.pressed(KeyCode::ShiftLeft) {
-        add += Vec3::NEG_Y * SPEED * time.delta_seconds();
+pub(crate) fn attach_npc_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    npcs: Query<(Entity, &Npc), Added<Npc>>,
+) {
+    for (entity, npc) in npcs.iter() {
+        commands.entity(entity).insert((
+            meshes.add(Capsule3d::new(0.4, 1.2)),
+            materials.add(npc.element.color()),
+            VisibilityBundle::default(),
+        ));
     }
+}
+
+#[derive(Component)]
+pub(crate) struct StatusIndicator;
 
-    cont.translation = Some(add);
+// a row of little orbs above anything with statuses, one per status. player
+// ids carry statuses too but aren't anywhere to put orbs over
+pub(crate) fn show_status_indicators(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    targets: Query<(Entity, &VisibleStatuses, Option<&Children>),
+        (Changed<VisibleStatuses>, With<GlobalTransform>)>,
+    indicators: Query<(), With<StatusIndicator>>,
+) {
+    const SPACING: f32 = 0.3;
+    for (entity, statuses, children) in targets.iter() {
+        for &child in children.map(|c| c.iter()).into_iter().flatten() {
+            if indicators.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+
+        let width = SPACING * statuses.0.len().saturating_sub(1) as f32;
+        commands.entity(entity).with_children(|cs| {
+            for (i, kind) in statuses.0.iter().enumerate() {
+                let color = kind.color();
+                cs.spawn((PbrBundle {
+                    mesh: meshes.add(Sphere::new(0.1)),
+                    material: materials.add(StandardMaterial {
+                        base_color: color,
+                        emissive: color,
+                        ..default()
+                    }),
+                    transform: Transform::from_xyz(
+                        i as f32 * SPACING - width / 2.0, 2.5, 0.0),
+                    ..default()
+                }, StatusIndicator));
+            }
+        });
+    }
 }
 
 #[derive(Default)]
-pub(crate) struct NoiseChunkGen {
+pub struct NoiseChunkGen {
     noise: Perlin,
 }
 
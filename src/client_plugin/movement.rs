@@ -0,0 +1,109 @@
+use crate::*;
+
+pub const GRAVITY: f32 = 25.0;
+pub const MAX_FALL_SPEED: f32 = 50.0;
+pub const JUMP_SPEED: f32 = 9.0;
+
+pub const WALK_SPEED: f32 = 8.0;
+pub const FLY_SPEED: f32 = 15.0;
+pub const SPRINT_MULTIPLIER: f32 = 1.6;
+pub const CROUCH_MULTIPLIER: f32 = 0.5;
+
+// how fast walking speed catches up with what the keys want, per second
+pub const ACCELERATION: f32 = 12.0;
+// the fraction of that left once off the ground
+pub const AIR_CONTROL: f32 = 0.3;
+
+// where the camera sits above the middle of the player
+pub const EYE_HEIGHT: f32 = 1.6;
+pub const CROUCH_EYE_HEIGHT: f32 = 0.8;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MoveMode {
+    #[default]
+    Walk,
+    // the old noclip flycam, for spectating
+    Fly,
+}
+
+// what the keys are asking for this frame
+#[derive(Copy, Clone, Debug)]
+pub struct MoveInput {
+    // which way to go, at most 1 long for full speed. up and down only
+    // count while flying
+    pub wish: Vec3,
+    pub jump: bool,
+    pub sprint: bool,
+    pub crouch: bool,
+    // from statuses like being frozen
+    pub speed: f32,
+    // whether there's space above to stand back up in
+    pub headroom: bool,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct PlayerMovement {
+    pub mode: MoveMode,
+    pub velocity: Vec3,
+    pub grounded: bool,
+    pub crouching: bool,
+}
+
+impl PlayerMovement {
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            MoveMode::Walk => MoveMode::Fly,
+            MoveMode::Fly => MoveMode::Walk,
+        };
+        self.velocity = Vec3::ZERO;
+    }
+
+    // bumping into something overhead stops a jump dead
+    pub fn hit_ceiling(&mut self) {
+        self.velocity.y = self.velocity.y.min(0.0);
+    }
+
+    // how far to move this frame. `grounded` is whether the controller
+    // ended up on the ground last frame
+    pub fn step(&mut self, input: MoveInput, grounded: bool, dt: f32) -> Vec3 {
+        let flat = Vec3::new(input.wish.x, 0.0, input.wish.z)
+            .clamp_length_max(1.0);
+        self.grounded = grounded;
+
+        if self.mode == MoveMode::Fly {
+            self.crouching = false;
+            self.velocity = Vec3::ZERO;
+            let up = Vec3::Y * input.wish.y.clamp(-1.0, 1.0);
+            return (flat + up) * FLY_SPEED * input.speed * dt;
+        }
+
+        // stuck crouching under anything too low to stand up in
+        self.crouching = input.crouch || (self.crouching && !input.headroom);
+        let speed = WALK_SPEED * input.speed * if self.crouching {
+            CROUCH_MULTIPLIER
+        } else if input.sprint {
+            SPRINT_MULTIPLIER
+        } else {
+            1.0
+        };
+
+        let control = if grounded { 1.0 } else { AIR_CONTROL };
+        let blend = (ACCELERATION * control * dt).min(1.0);
+        let horizontal = Vec3::new(self.velocity.x, 0.0, self.velocity.z)
+            .lerp(flat * speed, blend);
+
+        let mut fall = self.velocity.y;
+        if grounded && fall < 0.0 {
+            fall = 0.0;
+        }
+        if grounded && input.jump && !self.crouching {
+            fall = JUMP_SPEED;
+        }
+        // still pulled down on the ground, so the controller keeps
+        // noticing it's there
+        fall = (fall - GRAVITY * dt).max(-MAX_FALL_SPEED);
+
+        self.velocity = horizontal + Vec3::Y * fall;
+        self.velocity * dt
+    }
+}
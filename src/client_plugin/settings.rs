@@ -0,0 +1,107 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+use client_plugin::Player;
+use client_plugin::input::DEFAULT_INPUT_PATH;
+use net::config::ConfigError;
+use voxel::components::ChunkLoader;
+
+pub const DEFAULT_VIDEO_PATH: &str = "video.ron";
+
+pub const MIN_RENDER_DISTANCE: i32 = 2;
+pub const MAX_RENDER_DISTANCE: i32 = 32;
+pub const MIN_FOV: f32 = 30.0;
+pub const MAX_FOV: f32 = 120.0;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct VideoSettings {
+    // how many chunks out from the player to load
+    pub render_distance: i32,
+    pub fov_degrees: f32,
+    pub head_bob: bool,
+    pub show_fps: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            render_distance: 10,
+            fov_degrees: 45.0,
+            head_bob: true,
+            show_fps: true,
+        }
+    }
+}
+
+impl VideoSettings {
+    pub fn load(path: &Path) -> Result<VideoSettings, ConfigError> {
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(VideoSettings::default());
+            }
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+
+        let settings: VideoSettings = ron::from_str(&src)
+            .map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+
+        let distances = MIN_RENDER_DISTANCE..=MAX_RENDER_DISTANCE;
+        if !distances.contains(&settings.render_distance) {
+            return Err(ConfigError::Invalid(
+                "render distance must be between 2 and 32 chunks"));
+        }
+        if !(MIN_FOV..=MAX_FOV).contains(&settings.fov_degrees) {
+            return Err(ConfigError::Invalid(
+                "fov must be between 30 and 120 degrees"));
+        }
+
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        write_ron(self, path)
+    }
+}
+
+pub(crate) fn write_ron(
+    value: &impl Serialize,
+    path: &Path,
+) -> Result<(), ConfigError> {
+    let src = ron::ser::to_string_pretty(value, Default::default())
+        .map_err(|e| ConfigError::Io(path.to_owned(), io::Error::other(e)))?;
+    std::fs::write(path, src)
+        .map_err(|e| ConfigError::Io(path.to_owned(), e))
+}
+
+// where the settings came from, so the menu can save them back
+#[derive(Resource, Clone, Debug)]
+pub struct SettingsPaths {
+    pub input: PathBuf,
+    pub video: PathBuf,
+}
+
+impl Default for SettingsPaths {
+    fn default() -> Self {
+        SettingsPaths {
+            input: DEFAULT_INPUT_PATH.into(),
+            video: DEFAULT_VIDEO_PATH.into(),
+        }
+    }
+}
+
+pub(crate) fn apply_render_distance(
+    video: Res<VideoSettings>,
+    mut loaders: Query<&mut ChunkLoader, With<Player>>,
+) {
+    if !video.is_changed() {
+        return;
+    }
+
+    for mut loader in loaders.iter_mut() {
+        loader.x_radius = video.render_distance;
+        loader.z_radius = video.render_distance;
+    }
+}
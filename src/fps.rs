@@ -2,6 +2,7 @@
 use bevy::diagnostic::DiagnosticsStore;
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use magic_game::*;
+use client_plugin::settings::VideoSettings;
 
 /// Marker to find the container entity so we can show/hide the FPS counter
 #[derive(Component)]
@@ -123,6 +124,24 @@ pub fn fps_text_update_system(
     }
 }
 
+// follows the setting in the pause menu
+pub fn show_fps_counter(
+    video: Res<VideoSettings>,
+    mut q: Query<&mut Visibility, With<FpsRoot>>,
+) {
+    if !video.is_changed() {
+        return;
+    }
+
+    for mut vis in q.iter_mut() {
+        *vis = if video.show_fps {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
 /// Toggle the FPS counter when pressing F12
 fn _fps_counter_showhide(
     mut q: Query<&mut Visibility, With<FpsRoot>>,
@@ -4,11 +4,15 @@ pub use bevy_rapier3d::prelude::*;
 pub use lightyear::prelude::*;
 
 use client_plugin::NoiseChunkGen;
+use magic::MagicPlugin;
+use npc::NpcClientPlugin;
 use voxel::VoxelPlugin;
 
 pub mod client_plugin;
+pub mod hash;
 pub mod magic;
 pub mod net;
+pub mod npc;
 pub mod voxel;
 pub mod version;
 
@@ -18,12 +22,48 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugins(VoxelPlugin::new(NoiseChunkGen::default()))
+            .add_plugins(MagicPlugin)
+            .add_plugins(NpcClientPlugin)
+            .init_resource::<client_plugin::input::InputSettings>()
+            .init_resource::<client_plugin::input::ActionState>()
+            .init_resource::<client_plugin::settings::VideoSettings>()
+            .init_resource::<client_plugin::settings::SettingsPaths>()
+            .init_resource::<client_plugin::menu::Menu>()
+            .add_systems(PreUpdate, (
+                client_plugin::input::update_actions,
+                client_plugin::input::update_look,
+            ).after(bevy::input::InputSystem))
             .add_systems(Startup, (
                 client_plugin::setup_player,
-                client_plugin::setup_scene))
+                client_plugin::setup_scene,
+                client_plugin::hud::setup_hud))
             .add_systems(Update, (
-                client_plugin::handle_input,
-                client_plugin::handle_mouse))
+                (
+                    client_plugin::menu::handle_menu_keys,
+                    client_plugin::menu::press_menu_buttons,
+                    client_plugin::menu::sync_pause,
+                    client_plugin::menu::rebuild_menu,
+                    client_plugin::menu::highlight_buttons,
+                ).chain(),
+                client_plugin::settings::apply_render_distance,
+                (
+                    (
+                        client_plugin::handle_input,
+                        client_plugin::fit_player_to_stance,
+                    ).chain(),
+                    client_plugin::handle_mouse,
+                    client_plugin::handle_casting,
+                    client_plugin::click_to_move,
+                ).after(client_plugin::menu::sync_pause),
+                client_plugin::camera::update_camera,
+                client_plugin::attach_projectile_meshes,
+                client_plugin::attach_debris_meshes,
+                client_plugin::attach_npc_meshes,
+                client_plugin::show_status_indicators,
+                client_plugin::hud::update_hud_bars,
+                client_plugin::hud::rebuild_spell_slots,
+                client_plugin::hud::update_cooldown_sweeps,
+                client_plugin::hud::rebuild_status_icons))
         ;
     }
 }
@@ -0,0 +1,215 @@
+use std::fmt;
+use std::io;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::utils::{BoxedFuture, HashSet};
+
+use crate::*;
+use magic::runes::{RuneError, SpellRunes};
+use magic::spells::{self, Spell, SpellError, Spells};
+use net::handshake::{LoadingRegistries, Registries};
+
+pub const DEFAULT_SPELL_BOOK: &str = "spells/default.spells.ron";
+
+pub const DEFAULT_SPELLS: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"), "/assets/spells/default.spells.ron"));
+
+// a file of spells. anything in it replaces the built in spell of the same
+// name, and anything new is added on
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct SpellBook {
+    pub spells: Vec<Spell>,
+}
+
+#[derive(Deserialize)]
+struct SpellBookFile {
+    #[serde(default)]
+    spells: Vec<Spell>,
+    // spells written in runes, compiled as the book loads
+    #[serde(default)]
+    runes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum SpellBookError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Runes(String, RuneError),
+    Invalid(String, SpellError),
+    Duplicate(String),
+}
+
+impl fmt::Display for SpellBookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpellBookError::Io(e) => write!(f, "could not read spells: {}", e),
+            SpellBookError::Parse(e) =>
+                write!(f, "could not parse spells: {}", e),
+            SpellBookError::Runes(spell, e) =>
+                write!(f, "bad runes in {}: {}", spell, e),
+            SpellBookError::Invalid(spell, e) =>
+                write!(f, "invalid spell {}: {}", spell, e),
+            SpellBookError::Duplicate(spell) =>
+                write!(f, "{} is defined more than once", spell),
+        }
+    }
+}
+
+impl std::error::Error for SpellBookError {}
+
+impl From<io::Error> for SpellBookError {
+    fn from(e: io::Error) -> Self {
+        SpellBookError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for SpellBookError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        SpellBookError::Parse(e)
+    }
+}
+
+impl SpellBook {
+    pub fn from_ron(bytes: &[u8]) -> Result<SpellBook, SpellBookError> {
+        let file: SpellBookFile = ron::de::from_bytes(bytes)?;
+
+        let mut spells = file.spells;
+        for src in file.runes.iter() {
+            let runes = SpellRunes::parse(src)
+                .map_err(|e| SpellBookError::Runes(src.clone(), e))?;
+            let spell = runes.compile()
+                .map_err(|e| SpellBookError::Runes(runes.name.clone(), e))?;
+            spells.push(spell);
+        }
+
+        let mut names = HashSet::new();
+        for spell in spells.iter() {
+            spell.validate()
+                .map_err(|e| SpellBookError::Invalid(spell.name.clone(), e))?;
+            if !names.insert(spell.name.as_str()) {
+                return Err(SpellBookError::Duplicate(spell.name.clone()));
+            }
+        }
+
+        Ok(SpellBook { spells })
+    }
+}
+
+#[derive(Default)]
+pub struct SpellBookLoader;
+
+impl AssetLoader for SpellBookLoader {
+    type Asset = SpellBook;
+    type Settings = ();
+    type Error = SpellBookError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<SpellBook, SpellBookError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            SpellBook::from_ron(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["spells.ron"]
+    }
+}
+
+// keeps the book loaded, and watched for changes
+#[derive(Resource)]
+pub struct SpellBookHandle(pub Handle<SpellBook>);
+
+fn load_spell_book(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    loading: Option<ResMut<LoadingRegistries>>,
+) {
+    commands.insert_resource(SpellBookHandle(assets.load(DEFAULT_SPELL_BOOK)));
+    if let Some(mut loading) = loading {
+        loading.0.insert("spells".to_owned());
+    }
+}
+
+fn apply_spell_books(
+    mut events: EventReader<AssetEvent<SpellBook>>,
+    books: Res<Assets<SpellBook>>,
+    mut spells: ResMut<Spells>,
+    registries: Option<ResMut<Registries>>,
+) {
+    let mut changed = false;
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(book) = books.get(*id)
+        else {
+            continue;
+        };
+
+        for spell in book.spells.iter() {
+            spells.upsert(spell.clone());
+        }
+        info!("loaded {} spells", book.spells.len());
+        changed = true;
+    }
+
+    if changed {
+        if let Some(mut registries) = registries {
+            registries.insert("spells", spells.registry_hash());
+        }
+    }
+}
+
+// the spells hash isn't final until the book has loaded, or failed to
+fn finish_loading_spells(
+    mut events: EventReader<AssetEvent<SpellBook>>,
+    handle: Res<SpellBookHandle>,
+    assets: Res<AssetServer>,
+    loading: Option<ResMut<LoadingRegistries>>,
+) {
+    let loaded = events.read()
+        .any(|e| e.is_loaded_with_dependencies(&handle.0));
+    let failed = assets.load_state(&handle.0) == LoadState::Failed;
+    let Some(mut loading) = loading
+    else {
+        return;
+    };
+
+    if !loaded && !failed {
+        return;
+    }
+    if loading.0.remove("spells") && failed {
+        warn!("could not load {}, using the built in spells",
+            DEFAULT_SPELL_BOOK);
+    }
+}
+
+// needs the AssetPlugin, which the headless binaries have to add themselves
+pub struct SpellAssetPlugin;
+
+impl Plugin for SpellAssetPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<Spells>() {
+            let mut defaults = Spells::default();
+            spells::register_default_spells(&mut defaults);
+            app.insert_resource(defaults);
+        }
+
+        app
+            .init_asset::<SpellBook>()
+            .init_asset_loader::<SpellBookLoader>()
+            .add_systems(Startup, load_spell_book)
+            .add_systems(PreUpdate,
+                (apply_spell_books, finish_loading_spells).chain())
+        ;
+    }
+}
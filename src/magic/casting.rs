@@ -0,0 +1,335 @@
+use bevy::utils::{HashMap, HashSet};
+
+use crate::*;
+use magic::MagicElement;
+use magic::components::{Health, MagicCaster};
+use magic::damage::{DamageEvent, Dead};
+use magic::spells::{SpellEffect, SpellId, Spells, Targeting};
+use magic::status::{ApplyStatus, StatusEffects};
+use magic::terrain::terrain_rules;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SpellTarget {
+    Caster,
+    Entity(Entity),
+    Point(Vec3),
+    Direction(Vec3),
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CastSpell {
+    pub caster: Entity,
+    pub spell: SpellId,
+    pub target: SpellTarget,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct InterruptCast {
+    pub caster: Entity,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CastError {
+    UnknownSpell,
+    NotACaster,
+    AlreadyCasting,
+    OnCooldown,
+    NotEnoughMana,
+    BadTarget,
+    OutOfRange,
+    Dead,
+    Stunned,
+    // the spell reshapes terrain in a way the element it'd be cast in can't
+    WrongElement,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct CastFailed {
+    pub caster: Entity,
+    pub spell: SpellId,
+    pub error: CastError,
+}
+
+// sent once a spell finishes casting and its mana has been paid
+#[derive(Event, Clone, Debug)]
+pub struct SpellCast {
+    pub caster: Entity,
+    pub spell: SpellId,
+    pub element: MagicElement,
+    pub target: SpellTarget,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Casting {
+    pub spell: SpellId,
+    pub target: SpellTarget,
+    pub remaining: f32,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Cooldowns(pub HashMap<SpellId, f32>);
+
+impl Cooldowns {
+    pub fn remaining(&self, spell: SpellId) -> f32 {
+        self.0.get(&spell).cloned().unwrap_or(0.0)
+    }
+}
+
+// the spells a caster has ready, in hotbar order
+#[derive(Component, Clone, Debug, Default)]
+pub struct SpellSlots(pub Vec<SpellId>);
+
+fn validate_cast(
+    cast: &CastSpell,
+    spells: &Spells,
+    casters: &Query<(&MagicCaster, Option<&Casting>, Option<&Cooldowns>,
+        Option<&StatusEffects>, Has<Dead>)>,
+    positions: &Query<&GlobalTransform>,
+) -> Result<(), CastError> {
+    let spell = spells.get(cast.spell).ok_or(CastError::UnknownSpell)?;
+    let Ok((caster, casting, cooldowns, statuses, dead)) =
+        casters.get(cast.caster)
+    else {
+        return Err(CastError::NotACaster);
+    };
+
+    if dead {
+        return Err(CastError::Dead);
+    }
+    if statuses.is_some_and(|s| !s.can_cast()) {
+        return Err(CastError::Stunned);
+    }
+    if casting.is_some() {
+        return Err(CastError::AlreadyCasting);
+    }
+    if cooldowns.is_some_and(|c| c.remaining(cast.spell) > 0.0) {
+        return Err(CastError::OnCooldown);
+    }
+    if !caster.can_afford(&spell.cost) {
+        return Err(CastError::NotEnoughMana);
+    }
+
+    // rather than taking the mana and doing nothing
+    let element = spell.resolve_element(caster);
+    let inert = spell.effects.iter().any(|e| matches!(*e,
+        SpellEffect::Terraform { carve, .. }
+            if terrain_rules(element, carve).is_empty()));
+    if inert {
+        return Err(CastError::WrongElement);
+    }
+
+    let (range, point) = match (spell.targeting, cast.target) {
+        (Targeting::Caster, SpellTarget::Caster) => return Ok(()),
+        (Targeting::Direction, SpellTarget::Direction(_)) => return Ok(()),
+        (Targeting::Entity { range }, SpellTarget::Entity(target)) => {
+            let Ok(target) = positions.get(target)
+            else {
+                return Err(CastError::BadTarget);
+            };
+            (range, target.translation())
+        }
+        (Targeting::Point { range }, SpellTarget::Point(point)) =>
+            (range, point),
+        _ => return Err(CastError::BadTarget),
+    };
+
+    let from = positions.get(cast.caster)
+        .map(|t| t.translation())
+        .unwrap_or(point);
+    if from.distance(point) > range {
+        return Err(CastError::OutOfRange);
+    }
+
+    Ok(())
+}
+
+pub fn start_casts(
+    mut commands: Commands,
+    spells: Res<Spells>,
+    mut casts: EventReader<CastSpell>,
+    mut failed: EventWriter<CastFailed>,
+    casters: Query<(&MagicCaster, Option<&Casting>, Option<&Cooldowns>,
+        Option<&StatusEffects>, Has<Dead>)>,
+    positions: Query<&GlobalTransform>,
+) {
+    // Casting only shows up in the query once commands apply, so a second
+    // cast in the same tick has to be caught here
+    let mut started = HashSet::new();
+    for cast in casts.read() {
+        let result = if started.contains(&cast.caster) {
+            Err(CastError::AlreadyCasting)
+        } else {
+            validate_cast(cast, &spells, &casters, &positions)
+        };
+        if let Err(error) = result {
+            failed.send(CastFailed {
+                caster: cast.caster,
+                spell: cast.spell,
+                error,
+            });
+            continue;
+        }
+
+        started.insert(cast.caster);
+        let spell = cast.spell.spell(&spells);
+        commands.entity(cast.caster).insert(Casting {
+            spell: cast.spell,
+            target: cast.target,
+            remaining: spell.cast_time,
+        });
+    }
+}
+
+pub(crate) fn interrupt_casts(
+    mut commands: Commands,
+    mut interrupts: EventReader<InterruptCast>,
+) {
+    for i in interrupts.read() {
+        if let Some(mut caster) = commands.get_entity(i.caster) {
+            caster.remove::<Casting>();
+        }
+    }
+}
+
+pub(crate) fn tick_casts(
+    mut commands: Commands,
+    time: Res<Time>,
+    spells: Res<Spells>,
+    mut casters: Query<(Entity, &mut Casting, &mut MagicCaster,
+        Option<&mut Cooldowns>)>,
+    mut failed: EventWriter<CastFailed>,
+    mut cast: EventWriter<SpellCast>,
+) {
+    for (entity, mut casting, mut caster, cooldowns) in casters.iter_mut() {
+        casting.remaining -= time.delta_seconds();
+        if casting.remaining > 0.0 {
+            continue;
+        }
+
+        commands.entity(entity).remove::<Casting>();
+        let spell = casting.spell.spell(&spells);
+
+        // mana might have been drained while the spell was being cast
+        if !caster.spend(&spell.cost) {
+            failed.send(CastFailed {
+                caster: entity,
+                spell: casting.spell,
+                error: CastError::NotEnoughMana,
+            });
+            continue;
+        }
+
+        match cooldowns {
+            Some(mut cooldowns) => {
+                cooldowns.0.insert(casting.spell, spell.cooldown);
+            }
+            None => {
+                let mut cooldowns = Cooldowns::default();
+                cooldowns.0.insert(casting.spell, spell.cooldown);
+                commands.entity(entity).insert(cooldowns);
+            }
+        }
+
+        cast.send(SpellCast {
+            caster: entity,
+            spell: casting.spell,
+            element: spell.resolve_element(&caster),
+            target: casting.target,
+        });
+    }
+}
+
+pub(crate) fn tick_cooldowns(
+    time: Res<Time>,
+    mut cooldowns: Query<&mut Cooldowns>,
+) {
+    for mut cooldowns in cooldowns.iter_mut() {
+        cooldowns.0.retain(|_, remaining| {
+            *remaining -= time.delta_seconds();
+            *remaining > 0.0
+        });
+    }
+}
+
+pub(crate) fn apply_spell_effects(
+    spells: Res<Spells>,
+    mut cast: EventReader<SpellCast>,
+    mut damage: EventWriter<DamageEvent>,
+    mut statuses: EventWriter<ApplyStatus>,
+    mut healths: Query<&mut Health, Without<Dead>>,
+    mut casters: Query<&mut MagicCaster>,
+) {
+    for c in cast.read() {
+        let spell = c.spell.spell(&spells);
+        let hit = match c.target {
+            SpellTarget::Caster => Some(c.caster),
+            SpellTarget::Entity(e) => Some(e),
+            SpellTarget::Point(_) | SpellTarget::Direction(_) => None,
+        };
+
+        for effect in spell.effects.iter() {
+            let (effect, target) = match effect {
+                SpellEffect::OnCaster(inner) => (&**inner, Some(c.caster)),
+                effect => (effect, hit),
+            };
+
+            let Some(target) = target
+            else {
+                continue;
+            };
+
+            if let Some((kind, duration)) = effect.status(c.element) {
+                statuses.send(ApplyStatus {
+                    target,
+                    kind,
+                    duration,
+                    source: Some(c.caster),
+                });
+                continue;
+            }
+
+            match *effect {
+                SpellEffect::Damage { amount } => {
+                    damage.send(DamageEvent {
+                        target,
+                        amount,
+                        element: c.element,
+                        source: Some(c.caster),
+                    });
+                }
+
+                SpellEffect::Heal { amount } => {
+                    if let Ok(mut health) = healths.get_mut(target) {
+                        health.health = health.health.saturating_add(amount)
+                            .min(health.max_health);
+                    }
+                }
+
+                SpellEffect::RestoreMana { amount } => {
+                    if let Ok(mut caster) = casters.get_mut(target) {
+                        caster.restore(amount);
+                    }
+                }
+
+                SpellEffect::Overcharge { amount } => {
+                    if let Ok(mut caster) = casters.get_mut(target) {
+                        caster.overcharge(amount);
+                    }
+                }
+
+                // launched by projectile::spawn_projectiles
+                SpellEffect::Projectile { .. } => (),
+                // applied by terrain::apply_terrain_effects
+                SpellEffect::Terraform { .. } => (),
+                // handled above, or the element has no status to inflict
+                SpellEffect::Status { .. } => (),
+                // handled by magnet::spawn_magnetic_fields
+                SpellEffect::Magnet { .. } => (),
+                // set off by explosion::explode_spells, or by the projectile
+                SpellEffect::Explosion { .. } => (),
+                // unwrapped above; nesting them does nothing more
+                SpellEffect::OnCaster(_) => (),
+            }
+        }
+    }
+}
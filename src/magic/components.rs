@@ -1,7 +1,11 @@
 use crate::*;
 use magic::{ManaColor, MagicElement};
+use magic::spells::ManaCost;
 
-#[derive(Component)]
+// how far past their max pools can be overcharged
+pub const OVERCHARGE_PERCENT: u32 = 150;
+
+#[derive(Component, Clone, Debug)]
 pub struct MagicCaster {
     pub source_color_a: ManaColor,
     pub mana_a: u32,
@@ -14,9 +18,124 @@ pub struct MagicCaster {
     pub primary: MagicElement,
 }
 
-#[derive(Component)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MismatchedPrimary {
+    pub expected: MagicElement,
+    pub found: MagicElement,
+}
+
+impl MagicCaster {
+    pub fn new(
+        source_color_a: ManaColor,
+        source_color_b: ManaColor,
+        max_mana_a: u32,
+        max_mana_b: u32,
+    ) -> MagicCaster {
+        MagicCaster {
+            source_color_a,
+            mana_a: max_mana_a,
+            max_mana_a,
+            source_color_b,
+            mana_b: max_mana_b,
+            max_mana_b,
+            primary: MagicElement::from_colors(source_color_a, source_color_b),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), MismatchedPrimary> {
+        let expected =
+            MagicElement::from_colors(self.source_color_a, self.source_color_b);
+        if self.primary == expected {
+            Ok(())
+        } else {
+            Err(MismatchedPrimary { expected, found: self.primary })
+        }
+    }
+
+    // how much of `cost` each pool has to pay, or None if the caster can't
+    // channel one of the colours at all. costs that either pool could pay,
+    // such as black mana or a colour both pools share, come out of whichever
+    // pool has more to spare first
+    fn split_cost(&self, cost: &ManaCost) -> Option<(u32, u32)> {
+        let mut a = 0;
+        let mut b = 0;
+        let mut either = 0;
+        for color in ManaColor::ALL {
+            let amount = cost.get(color);
+            if amount == 0 {
+                continue;
+            }
+
+            match (color == self.source_color_a, color == self.source_color_b) {
+                (true, true) => either += amount,
+                (true, false) => a += amount,
+                (false, true) => b += amount,
+                (false, false) if color == ManaColor::Black => either += amount,
+                (false, false) => return None,
+            }
+        }
+
+        let spare_a = self.mana_a.saturating_sub(a);
+        let spare_b = self.mana_b.saturating_sub(b);
+        if spare_a >= spare_b {
+            let from_a = either.min(spare_a);
+            Some((a + from_a, b + either - from_a))
+        } else {
+            let from_b = either.min(spare_b);
+            Some((a + either - from_b, b + from_b))
+        }
+    }
+
+    pub fn can_afford(&self, cost: &ManaCost) -> bool {
+        self.split_cost(cost)
+            .is_some_and(|(a, b)| a <= self.mana_a && b <= self.mana_b)
+    }
+
+    pub fn spend(&mut self, cost: &ManaCost) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+
+        let (a, b) = self.split_cost(cost).unwrap();
+        self.mana_a -= a;
+        self.mana_b -= b;
+        true
+    }
+
+    pub fn restore(&mut self, amount: u32) {
+        self.mana_a = (self.mana_a + amount).min(self.max_mana_a);
+        self.mana_b = (self.mana_b + amount).min(self.max_mana_b);
+    }
+
+    // like restore, but lets the pools go past their max. the excess decays
+    // back down over time instead of regenerating
+    pub fn overcharge(&mut self, amount: u32) {
+        self.mana_a = (self.mana_a + amount)
+            .min(self.max_mana_a * OVERCHARGE_PERCENT / 100);
+        self.mana_b = (self.mana_b + amount)
+            .min(self.max_mana_b * OVERCHARGE_PERCENT / 100);
+    }
+
+    pub fn is_overcharged(&self) -> bool {
+        self.mana_a > self.max_mana_a || self.mana_b > self.max_mana_b
+    }
+}
+
+#[derive(Component, Clone, Debug)]
 pub struct Health {
     pub health: u32,
     pub max_health: u32,
     pub typed: MagicElement,
 }
+
+pub(crate) fn validate_casters(
+    mut casters: Query<(Entity, &mut MagicCaster), Changed<MagicCaster>>,
+) {
+    for (entity, mut caster) in casters.iter_mut() {
+        if let Err(e) = caster.validate() {
+            warn!("{:?} has primary element {:?} but its colours make {:?}",
+                entity, e.found, e.expected);
+            caster.primary = e.expected;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::utils::HashMap;
+
+use crate::*;
+use magic::MagicElement;
+use magic::casting::InterruptCast;
+use magic::components::Health;
+
+#[derive(Event, Clone, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    pub element: MagicElement,
+    pub source: Option<Entity>,
+}
+
+// sent the moment an entity's health hits zero
+#[derive(Event, Clone, Debug)]
+pub struct Died {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+#[derive(Component)]
+pub struct Dead;
+
+// how much of each element an entity shrugs off, from 0 (none) to 1 (all)
+#[derive(Component, Clone, Debug, Default)]
+pub struct Resistances(pub HashMap<MagicElement, f32>);
+
+impl Resistances {
+    pub fn get(&self, element: MagicElement) -> f32 {
+        self.0.get(&element).cloned().unwrap_or(0.0).clamp(0.0, 1.0)
+    }
+}
+
+// entities with this come back after dying instead of being despawned
+#[derive(Component, Clone, Debug)]
+pub struct Respawn {
+    pub point: Vec3,
+    pub delay: f32,
+}
+
+#[derive(Component)]
+pub struct RespawnTimer(pub f32);
+
+// damage multiplier for `attack` hitting something of element `defend`
+pub fn effectiveness(attack: MagicElement, defend: MagicElement) -> f32 {
+    use MagicElement::*;
+    match (attack, defend) {
+        (NonElemental, _) | (_, NonElemental) => 1.0,
+
+        (Water, Fire) | (Water, Lava) => 2.0,
+        (Fire, Water) | (Lava, Water) => 0.5,
+
+        (Electricity, Water) | (Electricity, Metal) => 2.0,
+        (Electricity, Earth) | (Electricity, Plant) => 0.5,
+
+        (Earth, Electricity) | (Earth, Fire) => 2.0,
+        (Earth, Plant) => 0.5,
+
+        (Fire, Plant) | (Fire, Ice) => 2.0,
+        (Fire, Earth) | (Fire, Lava) => 0.5,
+
+        (Ice, Plant) | (Ice, Water) => 2.0,
+        (Ice, Fire) | (Ice, Lava) => 0.5,
+
+        (Plant, Water) | (Plant, Earth) => 2.0,
+        (Plant, Fire) | (Plant, Metal) => 0.5,
+
+        (Lava, Ice) | (Lava, Metal) | (Lava, Plant) => 2.0,
+        (Lava, Earth) => 0.5,
+
+        (Metal, Ice) | (Metal, Earth) => 2.0,
+        (Metal, Electricity) | (Metal, Magnetism) => 0.5,
+
+        (Magnetism, Metal) | (Magnetism, Electricity) => 2.0,
+        (Magnetism, Earth) => 0.5,
+
+        (a, b) if a == b => 0.5,
+        _ => 1.0,
+    }
+}
+
+pub fn final_damage(
+    event: &DamageEvent,
+    health: &Health,
+    resistances: Option<&Resistances>,
+) -> u32 {
+    let resist = resistances.map(|r| r.get(event.element)).unwrap_or(0.0);
+    let scaled = event.amount as f32
+        * effectiveness(event.element, health.typed)
+        * (1.0 - resist);
+    scaled.round() as u32
+}
+
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damage: EventReader<DamageEvent>,
+    mut died: EventWriter<Died>,
+    mut interrupts: EventWriter<InterruptCast>,
+    mut targets: Query<(&mut Health, Option<&Resistances>), Without<Dead>>,
+) {
+    for event in damage.read() {
+        let Ok((mut health, resistances)) = targets.get_mut(event.target)
+        else {
+            continue;
+        };
+
+        // `Dead` only lands once commands apply, so a second hit this tick
+        // still gets through the filter
+        let before = health.health;
+        let amount = final_damage(event, &health, resistances);
+        if amount == 0 || before == 0 {
+            continue;
+        }
+
+        health.health = before.saturating_sub(amount);
+        interrupts.send(InterruptCast { caster: event.target });
+
+        if health.health == 0 {
+            commands.entity(event.target).insert(Dead);
+            died.send(Died {
+                entity: event.target,
+                killer: event.source,
+            });
+        }
+    }
+}
+
+pub(crate) fn handle_deaths(
+    mut commands: Commands,
+    mut died: EventReader<Died>,
+    respawns: Query<&Respawn>,
+) {
+    for d in died.read() {
+        match respawns.get(d.entity) {
+            Ok(respawn) => {
+                commands.entity(d.entity).insert(RespawnTimer(respawn.delay));
+            }
+            Err(_) => {
+                if let Some(e) = commands.get_entity(d.entity) {
+                    e.despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn tick_respawns(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut dead: Query<(Entity, &mut RespawnTimer, &Respawn, &mut Health,
+        &mut Transform)>,
+) {
+    for (entity, mut timer, respawn, mut health, mut trans) in dead.iter_mut() {
+        timer.0 -= time.delta_seconds();
+        if timer.0 > 0.0 {
+            continue;
+        }
+
+        health.health = health.max_health;
+        trans.translation = respawn.point;
+        commands.entity(entity)
+            .remove::<RespawnTimer>()
+            .remove::<Dead>();
+    }
+}
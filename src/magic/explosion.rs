@@ -0,0 +1,162 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::{SpellCast, SpellTarget};
+use magic::components::Health;
+use magic::damage::{DamageEvent, Dead};
+use magic::spells::{SpellEffect, Spells};
+use voxel::VoxelRes;
+use voxel::edit::{self, ProtectedRegions};
+
+// damage and impulse at the centre of an explosion, per point of power
+pub const DAMAGE_PER_POWER: f32 = 10.0;
+pub const IMPULSE_PER_POWER: f32 = 2.0;
+
+#[derive(Event, Clone, Debug)]
+pub struct Explosion {
+    pub center: Vec3,
+    pub radius: f32,
+    pub element: MagicElement,
+    // the hardest voxel it can break at the centre. everything else about
+    // it scales with this too
+    pub power: f32,
+    pub source: Option<Entity>,
+}
+
+impl Explosion {
+    // how much of the explosion reaches `pos`, from 1 at the centre to 0 at
+    // the edge
+    pub fn falloff(&self, pos: Vec3) -> f32 {
+        (1.0 - pos.distance(self.center) / self.radius).max(0.0)
+    }
+}
+
+// spells that explode go off at their target, unless they launch a
+// projectile, which explodes where it lands instead
+pub(crate) fn explode_spells(
+    spells: Res<Spells>,
+    mut cast: EventReader<SpellCast>,
+    positions: Query<&GlobalTransform>,
+    mut explosions: EventWriter<Explosion>,
+) {
+    for c in cast.read() {
+        let spell = c.spell.spell(&spells);
+        let launches = spell.effects.iter()
+            .any(|e| matches!(e, SpellEffect::Projectile { .. }));
+        if launches {
+            continue;
+        }
+
+        for effect in spell.effects.iter() {
+            let SpellEffect::Explosion { radius, power } = *effect
+            else {
+                continue;
+            };
+
+            let at = |e: Entity| positions.get(e).ok().map(|t| t.translation());
+            let center = match c.target {
+                SpellTarget::Caster => at(c.caster),
+                SpellTarget::Entity(e) => at(e),
+                SpellTarget::Point(point) => Some(point),
+                SpellTarget::Direction(dir) => at(c.caster)
+                    .map(|from| from + dir.normalize_or_zero() * radius),
+            };
+            let Some(center) = center
+            else {
+                continue;
+            };
+
+            explosions.send(Explosion {
+                center,
+                radius,
+                element: c.element,
+                power,
+                source: Some(c.caster),
+            });
+        }
+    }
+}
+
+pub(crate) fn explode_terrain(
+    voxels: Res<VoxelRes>,
+    protected: Res<ProtectedRegions>,
+    mut explosions: EventReader<Explosion>,
+) {
+    if explosions.is_empty() {
+        return;
+    }
+
+    let Ok(mut voxels) = voxels.write()
+    else {
+        return;
+    };
+
+    for e in explosions.read() {
+        edit::blast(&mut voxels, &protected, e.center, e.radius, e.power);
+    }
+}
+
+pub(crate) fn push_bodies(
+    mut commands: Commands,
+    mut explosions: EventReader<Explosion>,
+    mut bodies: Query<(Entity, &GlobalTransform, &RigidBody,
+        Option<&mut ExternalImpulse>)>,
+) {
+    for e in explosions.read() {
+        for (entity, trans, body, impulse) in bodies.iter_mut() {
+            let pos = trans.translation();
+            let falloff = e.falloff(pos);
+            if *body != RigidBody::Dynamic || falloff <= 0.0 {
+                continue;
+            }
+
+            let dir = (pos - e.center).try_normalize().unwrap_or(Vec3::Y);
+            let push = dir * e.power * IMPULSE_PER_POWER * falloff;
+            match impulse {
+                Some(mut impulse) => impulse.impulse += push,
+                None => {
+                    commands.entity(entity).insert(ExternalImpulse {
+                        impulse: push,
+                        ..default()
+                    });
+                }
+            }
+        }
+    }
+}
+
+// walls the explosion didn't break keep whatever's behind them safe
+pub(crate) fn damage_in_blast(
+    voxels: Res<VoxelRes>,
+    mut explosions: EventReader<Explosion>,
+    targets: Query<(Entity, &GlobalTransform), (With<Health>, Without<Dead>)>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    if explosions.is_empty() {
+        return;
+    }
+
+    let Ok(voxels) = voxels.read()
+    else {
+        return;
+    };
+
+    for e in explosions.read() {
+        for (entity, trans) in targets.iter() {
+            let pos = trans.translation();
+            let falloff = e.falloff(pos);
+            if falloff <= 0.0 || !voxels.line_of_sight(e.center, pos) {
+                continue;
+            }
+
+            let amount = (e.power * DAMAGE_PER_POWER * falloff).round() as u32;
+            if amount > 0 {
+                damage.send(DamageEvent {
+                    target: entity,
+                    amount,
+                    element: e.element,
+                    source: e.source,
+                });
+            }
+        }
+    }
+}
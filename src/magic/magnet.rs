@@ -0,0 +1,271 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::{SpellCast, SpellTarget};
+use magic::projectile::Projectile;
+use magic::spells::{SpellEffect, Spells};
+use voxel::{voxel_to_world, world_to_voxel, VoxelId, VoxelRes, VOXEL_SIZE};
+use voxel::debris::debris_bundle;
+use voxel::edit::ProtectedRegions;
+use voxel::sim::NEIGHBORS;
+
+pub const GRAVITY: f32 = 9.81;
+
+// how many ticks between fields tearing metal out of the terrain
+pub const DISLODGE_INTERVAL: u32 = 16;
+// the most voxels a field tears out at a time
+pub const MAX_DISLODGE: usize = 2;
+// fields weaker than this can't pull metal out of the ground
+pub const DISLODGE_STRENGTH: f32 = 10.0;
+
+// slows levitating bodies down so they settle instead of bobbing forever
+pub const LEVITATE_DAMPING: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MagnetMode {
+    Pull,
+    Push,
+    // holds bodies up in the middle of the field
+    Levitate,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Falloff {
+    Constant,
+    Linear,
+    InverseSquare,
+}
+
+impl Falloff {
+    // how much of a field's strength is left `distance` from its centre
+    pub fn factor(self, distance: f32, radius: f32) -> f32 {
+        if distance > radius {
+            return 0.0;
+        }
+
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - distance / radius,
+            // in voxels, so something sitting right on the centre doesn't
+            // get an infinite kick
+            Falloff::InverseSquare =>
+                1.0 / (1.0 + (distance / VOXEL_SIZE).powi(2)),
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MagneticField {
+    pub mode: MagnetMode,
+    // acceleration at the centre, in m/s²
+    pub strength: f32,
+    pub radius: f32,
+    pub falloff: Falloff,
+}
+
+impl MagneticField {
+    // the acceleration the field gives a metal body at `pos`. like gravity
+    // it's the same whatever the body weighs
+    pub fn acceleration(&self, centre: Vec3, pos: Vec3, vel: Vec3) -> Vec3 {
+        let offset = centre - pos;
+        let factor = self.falloff.factor(offset.length(), self.radius);
+        if factor <= 0.0 {
+            return Vec3::ZERO;
+        }
+
+        let towards = offset.normalize_or_zero();
+        match self.mode {
+            MagnetMode::Pull => towards * self.strength * factor,
+            MagnetMode::Push => -towards * self.strength * factor,
+            MagnetMode::Levitate => Vec3::Y * GRAVITY
+                + offset * self.strength * factor
+                - vel * LEVITATE_DAMPING,
+        }
+    }
+}
+
+// dynamic bodies that magnetic fields act on
+#[derive(Component)]
+pub struct Metallic;
+
+#[derive(Component)]
+pub struct FieldLifetime(pub f32);
+
+pub(crate) fn spawn_magnetic_fields(
+    mut commands: Commands,
+    spells: Res<Spells>,
+    mut cast: EventReader<SpellCast>,
+    positions: Query<&GlobalTransform>,
+) {
+    for c in cast.read() {
+        let spell = c.spell.spell(&spells);
+        for effect in spell.effects.iter() {
+            let SpellEffect::Magnet {
+                mode, strength, radius, falloff, duration,
+            } = *effect
+            else {
+                continue;
+            };
+
+            let field = (
+                MagneticField { mode, strength, radius, falloff },
+                FieldLifetime(duration),
+            );
+
+            // fields cast on something move around with it
+            let (parent, at) = match c.target {
+                SpellTarget::Caster => (Some(c.caster), Vec3::ZERO),
+                SpellTarget::Entity(e) => (Some(e), Vec3::ZERO),
+                SpellTarget::Point(point) => (None, point),
+                SpellTarget::Direction(dir) => {
+                    let Ok(from) = positions.get(c.caster)
+                    else {
+                        continue;
+                    };
+                    let ahead = dir.normalize_or_zero() * radius;
+                    (None, from.translation() + ahead)
+                }
+            };
+
+            let transform = TransformBundle::from_transform(
+                Transform::from_translation(at));
+            match parent.and_then(|p| commands.get_entity(p)) {
+                Some(mut parent) => {
+                    parent.with_children(|cs| {
+                        cs.spawn((transform, field));
+                    });
+                }
+                None if parent.is_none() => {
+                    commands.spawn((transform, field));
+                }
+                None => (),
+            }
+        }
+    }
+}
+
+// metal projectiles get pushed around by magnets too
+pub(crate) fn tag_metal_projectiles(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &Projectile), Added<Projectile>>,
+) {
+    for (entity, projectile) in projectiles.iter() {
+        if projectile.element == MagicElement::Metal {
+            commands.entity(entity).insert(Metallic);
+        }
+    }
+}
+
+pub(crate) fn prepare_metallic_bodies(
+    mut commands: Commands,
+    bodies: Query<Entity, (Added<Metallic>, Without<ExternalForce>)>,
+) {
+    for entity in bodies.iter() {
+        commands.entity(entity).insert((
+            ExternalForce::default(),
+            ReadMassProperties::default(),
+        ));
+    }
+}
+
+// fields that pull or lift tear exposed metal out of the terrain, nearest
+// first, as debris they can then move
+pub(crate) fn dislodge_metal(
+    mut commands: Commands,
+    mut ticks: Local<u32>,
+    voxels: Res<VoxelRes>,
+    protected: Res<ProtectedRegions>,
+    fields: Query<(&MagneticField, &GlobalTransform)>,
+) {
+    *ticks += 1;
+    if *ticks % DISLODGE_INTERVAL != 0 || fields.is_empty() {
+        return;
+    }
+
+    let Ok(mut voxels) = voxels.write()
+    else {
+        return;
+    };
+    let Some(metal) = voxels.id_from_name("metal")
+    else {
+        return;
+    };
+
+    for (field, trans) in fields.iter() {
+        let weak = field.strength < DISLODGE_STRENGTH;
+        if field.mode == MagnetMode::Push || weak {
+            continue;
+        }
+
+        let centre = trans.translation();
+        let c = world_to_voxel(centre);
+        let r = (field.radius / VOXEL_SIZE).ceil() as i32;
+        let mut loose = Vec::new();
+        for x in -r..=r {
+            for y in -r..=r {
+                for z in -r..=r {
+                    let pos = c + IVec3::new(x, y, z);
+                    if voxels.get_block(pos.x, pos.y, pos.z) != metal
+                        || protected.is_protected(pos)
+                    {
+                        continue;
+                    }
+
+                    let distance = voxel_to_world(pos).distance(centre);
+                    if distance > field.radius {
+                        continue;
+                    }
+
+                    let exposed = NEIGHBORS.iter().any(|&n| {
+                        let n = pos + n;
+                        !voxels.get_block(n.x, n.y, n.z).config(&voxels).solid
+                    });
+                    if exposed {
+                        loose.push((distance, pos.to_array()));
+                    }
+                }
+            }
+        }
+
+        loose.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        for (_, [x, y, z]) in loose.into_iter().take(MAX_DISLODGE) {
+            if !voxels.edit_block(x, y, z, VoxelId::air()) {
+                continue;
+            }
+            let pos = IVec3::new(x, y, z);
+            if let Some(debris) = debris_bundle(&[(pos, metal)]) {
+                commands.spawn((debris, Metallic));
+            }
+        }
+    }
+}
+
+pub(crate) fn apply_magnetic_forces(
+    fields: Query<(&MagneticField, &GlobalTransform)>,
+    mut bodies: Query<(&GlobalTransform, &Velocity, &ReadMassProperties,
+        &mut ExternalForce), With<Metallic>>,
+) {
+    for (trans, vel, mass, mut force) in bodies.iter_mut() {
+        let pos = trans.translation();
+        let accel: Vec3 = fields.iter()
+            .map(|(f, t)| f.acceleration(t.translation(), pos, vel.linvel))
+            .sum();
+
+        let wanted = accel * mass.get().mass;
+        if force.force != wanted {
+            force.force = wanted;
+        }
+    }
+}
+
+pub(crate) fn tick_fields(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut fields: Query<(Entity, &mut FieldLifetime)>,
+) {
+    for (entity, mut lifetime) in fields.iter_mut() {
+        lifetime.0 -= time.delta_seconds();
+        if lifetime.0 <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
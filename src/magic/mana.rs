@@ -0,0 +1,183 @@
+use crate::*;
+use magic::ManaColor;
+use magic::components::MagicCaster;
+use voxel::{VoxelRes, world_to_voxel};
+
+// regeneration is done in thousandths of a point of mana so the pools stay
+// integers and every tick adds exactly the same amount on every machine
+pub const MILLI: u32 = 1000;
+
+// how far around a caster voxels count towards its environment bonus
+pub const ENVIRONMENT_RADIUS: i32 = 4;
+
+// how many ticks between environment scans
+pub const ENVIRONMENT_INTERVAL: u32 = 16;
+
+// overcharged pools lose this many millimana a tick until back at max
+pub const OVERCHARGE_DECAY: u32 = 50;
+
+#[derive(Component, Clone, Debug)]
+pub struct ManaRegen {
+    // millimana per tick
+    pub rate_a: u32,
+    pub rate_b: u32,
+
+    // percent bonus from nearby voxels of the pool's colour, 0..=100
+    pub environment_a: u32,
+    pub environment_b: u32,
+
+    acc_a: u32,
+    acc_b: u32,
+    empty_a: bool,
+    empty_b: bool,
+}
+
+impl ManaRegen {
+    pub fn new(rate_a: u32, rate_b: u32) -> ManaRegen {
+        ManaRegen {
+            rate_a,
+            rate_b,
+            environment_a: 0,
+            environment_b: 0,
+            acc_a: 0,
+            acc_b: 0,
+            empty_a: false,
+            empty_b: false,
+        }
+    }
+
+    // convenience for rates given in mana per second
+    pub fn per_second(rate_a: f32, rate_b: f32, tick: f32) -> ManaRegen {
+        ManaRegen::new(
+            (rate_a * tick * MILLI as f32).round() as u32,
+            (rate_b * tick * MILLI as f32).round() as u32,
+        )
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct ManaDepleted {
+    pub entity: Entity,
+    pub color: ManaColor,
+}
+
+fn regen_pool(
+    mana: &mut u32,
+    max: u32,
+    acc: &mut u32,
+    rate: u32,
+    environment: u32,
+) {
+    if *mana > max {
+        // overcharge bleeds off instead of regenerating
+        *acc += OVERCHARGE_DECAY;
+        let lost = *acc / MILLI;
+        *acc %= MILLI;
+        *mana = mana.saturating_sub(lost).max(max);
+        return;
+    }
+
+    if *mana == max {
+        *acc = 0;
+        return;
+    }
+
+    *acc += rate + rate * environment / 100;
+    *mana = (*mana + *acc / MILLI).min(max);
+    *acc %= MILLI;
+}
+
+pub(crate) fn regenerate_mana(
+    mut casters: Query<(&mut MagicCaster, &mut ManaRegen)>,
+) {
+    for (mut caster, mut regen) in casters.iter_mut() {
+        // most ticks only move the accumulators, and casters are only
+        // marked changed when there's actually more or less mana
+        let c = caster.bypass_change_detection();
+        let regen = &mut *regen;
+        let before = (c.mana_a, c.mana_b);
+        regen_pool(&mut c.mana_a, c.max_mana_a, &mut regen.acc_a,
+            regen.rate_a, regen.environment_a);
+        regen_pool(&mut c.mana_b, c.max_mana_b, &mut regen.acc_b,
+            regen.rate_b, regen.environment_b);
+        if (c.mana_a, c.mana_b) != before {
+            caster.set_changed();
+        }
+    }
+}
+
+pub(crate) fn detect_depletion(
+    mut casters: Query<(Entity, &MagicCaster, &mut ManaRegen),
+        Changed<MagicCaster>>,
+    mut depleted: EventWriter<ManaDepleted>,
+) {
+    for (entity, caster, mut regen) in casters.iter_mut() {
+        let empty_a = caster.mana_a == 0;
+        if empty_a && !regen.empty_a {
+            depleted.send(ManaDepleted {
+                entity,
+                color: caster.source_color_a,
+            });
+        }
+        regen.empty_a = empty_a;
+
+        let empty_b = caster.mana_b == 0;
+        if empty_b && !regen.empty_b {
+            depleted.send(ManaDepleted {
+                entity,
+                color: caster.source_color_b,
+            });
+        }
+        regen.empty_b = empty_b;
+    }
+}
+
+pub(crate) fn scan_environment(
+    mut ticks: Local<u32>,
+    voxels: Option<Res<VoxelRes>>,
+    mut casters: Query<(&MagicCaster, &mut ManaRegen, &GlobalTransform)>,
+) {
+    *ticks += 1;
+    if *ticks < ENVIRONMENT_INTERVAL {
+        return;
+    }
+
+    let Some(voxels) = voxels
+    else {
+        return;
+    };
+    // a scan that finds the voxels busy stays due and tries again next tick
+    let Ok(voxels) = voxels.try_read()
+    else {
+        return;
+    };
+    *ticks = 0;
+
+    let side = 2 * ENVIRONMENT_RADIUS + 1;
+    let volume = (side * side * side) as u32;
+    for (caster, mut regen, trans) in casters.iter_mut() {
+        let centre = world_to_voxel(trans.translation());
+        let mut count_a = 0;
+        let mut count_b = 0;
+
+        for x in -ENVIRONMENT_RADIUS..=ENVIRONMENT_RADIUS {
+            for y in -ENVIRONMENT_RADIUS..=ENVIRONMENT_RADIUS {
+                for z in -ENVIRONMENT_RADIUS..=ENVIRONMENT_RADIUS {
+                    let voxel = voxels.get_block(
+                        centre.x + x, centre.y + y, centre.z + z);
+                    let color = voxel.config(&voxels).mana_color;
+                    if color == Some(caster.source_color_a) {
+                        count_a += 1;
+                    }
+                    if color == Some(caster.source_color_b) {
+                        count_b += 1;
+                    }
+                }
+            }
+        }
+
+        // being surrounded on all sides doubles regeneration
+        regen.environment_a = count_a * 100 / volume;
+        regen.environment_b = count_b * 100 / volume;
+    }
+}
@@ -1,7 +1,19 @@
+use crate::*;
+
+pub mod assets;
+pub mod casting;
 pub mod components;
+pub mod damage;
+pub mod explosion;
+pub mod magnet;
+pub mod mana;
+pub mod projectile;
+pub mod runes;
 pub mod spells;
+pub mod status;
+pub mod terrain;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ManaColor {
     Black,
     Red,
@@ -9,7 +21,25 @@ pub enum ManaColor {
     Blue,
 }
 
-#[derive(Clone, Copy)]
+impl ManaColor {
+    pub const ALL: [ManaColor; 4] = [
+        ManaColor::Black,
+        ManaColor::Red,
+        ManaColor::Yellow,
+        ManaColor::Blue,
+    ];
+
+    pub fn color(self) -> Color {
+        match self {
+            ManaColor::Black => Color::rgb_u8(60, 50, 70),
+            ManaColor::Red => Color::rgb_u8(210, 50, 40),
+            ManaColor::Yellow => Color::rgb_u8(240, 200, 40),
+            ManaColor::Blue => Color::rgb_u8(50, 100, 230),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MagicElement {
     // black
     NonElemental,
@@ -41,3 +71,131 @@ pub enum MagicElement {
     // uy
     Fire,
 }
+
+impl MagicElement {
+    // the element a caster channels from an ordered pair of mana colours.
+    // black mana is colourless, so it leaves the other colour's element
+    // untouched
+    pub const fn from_colors(a: ManaColor, b: ManaColor) -> MagicElement {
+        use ManaColor::*;
+        match (a, b) {
+            (Black, Black) => MagicElement::NonElemental,
+
+            (Red, Red) | (Red, Black) | (Black, Red) => MagicElement::Earth,
+            (Yellow, Yellow) | (Yellow, Black) | (Black, Yellow) =>
+                MagicElement::Electricity,
+            (Blue, Blue) | (Blue, Black) | (Black, Blue) => MagicElement::Water,
+
+            (Red, Yellow) => MagicElement::Magnetism,
+            (Red, Blue) => MagicElement::Ice,
+            (Yellow, Red) => MagicElement::Metal,
+            (Yellow, Blue) => MagicElement::Plant,
+            (Blue, Red) => MagicElement::Lava,
+            (Blue, Yellow) => MagicElement::Fire,
+        }
+    }
+}
+
+impl MagicElement {
+    // the simplest pair of mana colours that channels the element
+    pub const fn colors(self) -> (ManaColor, ManaColor) {
+        use ManaColor::*;
+        match self {
+            MagicElement::NonElemental => (Black, Black),
+            MagicElement::Earth => (Red, Red),
+            MagicElement::Electricity => (Yellow, Yellow),
+            MagicElement::Water => (Blue, Blue),
+            MagicElement::Magnetism => (Red, Yellow),
+            MagicElement::Ice => (Red, Blue),
+            MagicElement::Metal => (Yellow, Red),
+            MagicElement::Plant => (Yellow, Blue),
+            MagicElement::Lava => (Blue, Red),
+            MagicElement::Fire => (Blue, Yellow),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            MagicElement::NonElemental => Color::rgb_u8(80, 80, 80),
+            MagicElement::Earth => Color::rgb_u8(140, 90, 40),
+            MagicElement::Electricity => Color::rgb_u8(255, 240, 60),
+            MagicElement::Water => Color::rgb_u8(40, 110, 255),
+            MagicElement::Magnetism => Color::rgb_u8(200, 60, 200),
+            MagicElement::Ice => Color::rgb_u8(170, 230, 255),
+            MagicElement::Metal => Color::rgb_u8(170, 170, 180),
+            MagicElement::Plant => Color::rgb_u8(60, 190, 60),
+            MagicElement::Lava => Color::rgb_u8(255, 90, 20),
+            MagicElement::Fire => Color::rgb_u8(255, 140, 0),
+        }
+    }
+}
+
+pub struct MagicPlugin;
+
+impl Plugin for MagicPlugin {
+    fn build(&self, app: &mut App) {
+        let mut spells = spells::Spells::default();
+        spells::register_default_spells(&mut spells);
+
+        app
+            .insert_resource(spells)
+            .add_plugins(assets::SpellAssetPlugin)
+            .add_event::<casting::CastSpell>()
+            .add_event::<casting::InterruptCast>()
+            .add_event::<casting::CastFailed>()
+            .add_event::<casting::SpellCast>()
+            .add_event::<damage::DamageEvent>()
+            .add_event::<damage::Died>()
+            .add_event::<mana::ManaDepleted>()
+            .add_event::<projectile::ProjectileImpact>()
+            .add_event::<status::ApplyStatus>()
+            .add_event::<explosion::Explosion>()
+            .add_systems(PreUpdate, components::validate_casters)
+            .add_systems(Update, projectile::follow_projectile_motion)
+            .add_systems(FixedUpdate, (
+                (
+                    mana::scan_environment,
+                    mana::regenerate_mana,
+                ).chain(),
+                (
+                    casting::tick_cooldowns,
+                    casting::start_casts,
+                    casting::interrupt_casts,
+                    casting::tick_casts,
+                    casting::apply_spell_effects,
+                    terrain::apply_terrain_effects,
+                ).chain(),
+                (
+                    projectile::spawn_projectiles,
+                    projectile::tick_projectiles,
+                    projectile::handle_projectile_collisions,
+                    terrain::electrify_terrain,
+                    terrain::shock_charged_entities,
+                ).chain(),
+                (
+                    explosion::explode_spells,
+                    explosion::explode_terrain,
+                    explosion::push_bodies,
+                    explosion::damage_in_blast,
+                ).chain(),
+                (
+                    magnet::spawn_magnetic_fields,
+                    magnet::tag_metal_projectiles,
+                    magnet::prepare_metallic_bodies,
+                    magnet::dislodge_metal,
+                    magnet::apply_magnetic_forces,
+                    magnet::tick_fields,
+                ).chain(),
+                (
+                    status::apply_statuses,
+                    status::tick_statuses,
+                    damage::apply_damage,
+                    damage::handle_deaths,
+                    damage::tick_respawns,
+                    status::update_visible_statuses,
+                    mana::detect_depletion,
+                ).chain(),
+            ).chain())
+        ;
+    }
+}
@@ -0,0 +1,315 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::{SpellCast, SpellTarget};
+use magic::components::Health;
+use magic::damage::DamageEvent;
+use magic::explosion::Explosion;
+use magic::spells::{SpellEffect, Spells};
+use magic::status::{ApplyStatus, StatusKind};
+use voxel::{world_to_voxel, VOXEL_SIZE};
+use voxel::components::Chunk;
+
+// how far in front of the caster projectiles appear, so they don't hit them
+pub const SPAWN_OFFSET: f32 = 1.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+    // falls under gravity, like a thrown rock
+    Arcing,
+    // flies in a straight line
+    Straight,
+    // bounces off terrain a few times before bursting
+    Bouncing { bounces: u8 },
+}
+
+impl ProjectileKind {
+    pub fn for_element(element: MagicElement) -> ProjectileKind {
+        match element {
+            MagicElement::Earth
+            | MagicElement::Metal
+            | MagicElement::Lava => ProjectileKind::Arcing,
+
+            MagicElement::Water
+            | MagicElement::Plant => ProjectileKind::Bouncing { bounces: 3 },
+
+            MagicElement::NonElemental
+            | MagicElement::Electricity
+            | MagicElement::Magnetism
+            | MagicElement::Ice
+            | MagicElement::Fire => ProjectileKind::Straight,
+        }
+    }
+
+    fn gravity_scale(self) -> f32 {
+        match self {
+            ProjectileKind::Arcing => 1.0,
+            ProjectileKind::Straight => 0.0,
+            ProjectileKind::Bouncing { .. } => 0.5,
+        }
+    }
+
+    fn restitution(self) -> f32 {
+        match self {
+            ProjectileKind::Bouncing { .. } => 0.8,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    pub element: MagicElement,
+    pub damage: u32,
+    pub radius: f32,
+    // inflicted on whatever the projectile hits, with their durations
+    pub statuses: Vec<(StatusKind, f32)>,
+    // the radius and power it explodes with when it lands, if it does
+    pub explosion: Option<(f32, f32)>,
+}
+
+// where a projectile is and where it's going, kept up to date by the server
+// so clients can draw projectiles without simulating them
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProjectileMotion {
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+}
+
+// server side only, entities don't survive replication
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ProjectileOwner(pub Entity);
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ProjectileLifetime(pub f32);
+
+#[derive(Event, Clone, Debug)]
+pub struct ProjectileImpact {
+    pub projectile: Entity,
+    pub caster: Option<Entity>,
+    pub element: MagicElement,
+    pub position: Vec3,
+    pub normal: Vec3,
+    // the voxel that was hit, if it was terrain
+    pub voxel: Option<IVec3>,
+    // the entity that was hit, if it wasn't terrain
+    pub hit: Option<Entity>,
+}
+
+pub(crate) fn spawn_projectiles(
+    mut commands: Commands,
+    spells: Res<Spells>,
+    mut cast: EventReader<SpellCast>,
+    positions: Query<&GlobalTransform>,
+) {
+    for c in cast.read() {
+        let Ok(from) = positions.get(c.caster).map(|t| t.translation())
+        else {
+            continue;
+        };
+
+        let dir = match c.target {
+            SpellTarget::Caster => continue,
+            SpellTarget::Direction(dir) => dir,
+            SpellTarget::Point(point) => point - from,
+            SpellTarget::Entity(e) => {
+                let Ok(to) = positions.get(e)
+                else {
+                    continue;
+                };
+                to.translation() - from
+            }
+        }.normalize_or_zero();
+
+        if dir == Vec3::ZERO {
+            continue;
+        }
+
+        let spell = c.spell.spell(&spells);
+        let statuses: Vec<_> = spell.effects.iter()
+            .filter_map(|e| e.status(c.element))
+            .collect();
+        let explosion = spell.effects.iter().find_map(|e| e.explosion());
+        for effect in spell.effects.iter() {
+            let SpellEffect::Projectile { kind, speed, damage, radius, lifetime } =
+                *effect
+            else {
+                continue;
+            };
+
+            let kind = kind.unwrap_or(ProjectileKind::for_element(c.element));
+            let start = from + dir * SPAWN_OFFSET;
+            commands.spawn((
+                TransformBundle::from_transform(
+                    Transform::from_translation(start)),
+                Projectile {
+                    kind,
+                    element: c.element,
+                    damage,
+                    radius,
+                    statuses: statuses.clone(),
+                    explosion,
+                },
+                ProjectileMotion {
+                    position: start.into(),
+                    velocity: (dir * speed).into(),
+                },
+                ProjectileOwner(c.caster),
+                ProjectileLifetime(lifetime),
+                RigidBody::Dynamic,
+                Collider::ball(radius),
+                Velocity::linear(dir * speed),
+                GravityScale(kind.gravity_scale()),
+                Restitution::coefficient(kind.restitution()),
+                Ccd::enabled(),
+                ActiveEvents::COLLISION_EVENTS,
+            ));
+        }
+    }
+}
+
+pub(crate) fn handle_projectile_collisions(
+    mut commands: Commands,
+    rapier: Res<RapierContext>,
+    mut collisions: EventReader<CollisionEvent>,
+    mut projectiles: Query<(&mut Projectile, &Transform, &Velocity,
+        Option<&ProjectileOwner>)>,
+    chunks: Query<(), With<Chunk>>,
+    healths: Query<(), With<Health>>,
+    mut impacts: EventWriter<ProjectileImpact>,
+    mut damage: EventWriter<DamageEvent>,
+    mut statuses: EventWriter<ApplyStatus>,
+    mut explosions: EventWriter<Explosion>,
+) {
+    let mut burst = Vec::new();
+    for collision in collisions.read() {
+        let &CollisionEvent::Started(a, b, _) = collision
+        else {
+            continue;
+        };
+        if burst.contains(&a) || burst.contains(&b) {
+            continue;
+        }
+
+        let (projectile, other) = if projectiles.contains(a) {
+            (a, b)
+        } else if projectiles.contains(b) {
+            (b, a)
+        } else {
+            continue;
+        };
+
+        let Ok((mut p, trans, vel, owner)) = projectiles.get_mut(projectile)
+        else {
+            continue;
+        };
+        let caster = owner.map(|o| o.0);
+        if caster == Some(other) {
+            continue;
+        }
+
+        let is_terrain = chunks.contains(other);
+        if is_terrain {
+            if let ProjectileKind::Bouncing { bounces } = &mut p.kind {
+                if *bounces > 0 {
+                    *bounces -= 1;
+                    continue;
+                }
+            }
+        }
+
+        // find the surface that was hit by casting back along the path
+        let dir = vel.linvel.normalize_or_zero();
+        let back = trans.translation - dir * p.radius * 2.0;
+        let only_other = |e: Entity| e == other;
+        let filter = QueryFilter::default()
+            .exclude_collider(projectile)
+            .predicate(&only_other);
+        let (position, normal) = rapier
+            .cast_ray_and_get_normal(back, dir, p.radius * 4.0, true, filter)
+            .map(|(_, hit)| (hit.point, hit.normal))
+            .unwrap_or((trans.translation, -dir));
+
+        let voxel = is_terrain.then(||
+            world_to_voxel(position - normal * VOXEL_SIZE * 0.5));
+
+        if healths.contains(other) {
+            damage.send(DamageEvent {
+                target: other,
+                amount: p.damage,
+                element: p.element,
+                source: caster,
+            });
+            for &(kind, duration) in p.statuses.iter() {
+                statuses.send(ApplyStatus {
+                    target: other,
+                    kind,
+                    duration,
+                    source: caster,
+                });
+            }
+        }
+
+        if let Some((radius, power)) = p.explosion {
+            explosions.send(Explosion {
+                center: position,
+                radius,
+                element: p.element,
+                power,
+                source: caster,
+            });
+        }
+
+        impacts.send(ProjectileImpact {
+            projectile,
+            caster,
+            element: p.element,
+            position,
+            normal,
+            voxel,
+            hit: (!is_terrain).then_some(other),
+        });
+        commands.entity(projectile).despawn_recursive();
+        burst.push(projectile);
+    }
+}
+
+pub(crate) fn tick_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut ProjectileLifetime,
+        &mut ProjectileMotion, &Transform, &Velocity)>,
+) {
+    for (entity, mut lifetime, mut motion, trans, vel) in projectiles.iter_mut() {
+        lifetime.0 -= time.delta_seconds();
+        if lifetime.0 <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let position = trans.translation.into();
+        let velocity = vel.linvel.into();
+        if motion.position != position || motion.velocity != velocity {
+            *motion = ProjectileMotion { position, velocity };
+        }
+    }
+}
+
+// replicated projectiles have no physics of their own, they just follow
+// what the server says
+pub(crate) fn follow_projectile_motion(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &ProjectileMotion, Option<&mut Transform>),
+        (With<Projectile>, Without<ProjectileOwner>)>,
+) {
+    for (entity, motion, trans) in projectiles.iter_mut() {
+        let position = Vec3::from(motion.position);
+        match trans {
+            Some(mut trans) => trans.translation = position,
+            None => {
+                commands.entity(entity).insert(TransformBundle::from_transform(
+                    Transform::from_translation(position)));
+            }
+        }
+    }
+}
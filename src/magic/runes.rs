@@ -0,0 +1,560 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::*;
+use magic::MagicElement;
+use magic::spells::{ManaCost, Spell, SpellEffect, Targeting};
+use magic::status::StatusKind;
+use voxel::edit::VoxelShape;
+
+// a spell written out in runes looks like
+//
+//     fireball: bolt fire speed 1.5 on hit damage 20 on hit status
+//
+// a name, a shape, optionally an element (without one the spell takes on
+// the caster's), any modifiers, then what happens and when
+
+// modifiers can scale a spell's base numbers by this much either way
+pub const MIN_MODIFIER: f32 = 0.25;
+pub const MAX_MODIFIER: f32 = 4.0;
+
+pub const BOLT_SPEED: f32 = 30.0;
+pub const BOLT_RADIUS: f32 = 0.2;
+pub const BOLT_LIFETIME: f32 = 5.0;
+pub const CONE_RANGE: f32 = 8.0;
+pub const POINT_RANGE: f32 = 20.0;
+pub const SPHERE_RADIUS: f32 = 2.5;
+pub const WALL_RADIUS: f32 = 1.5;
+pub const WALL_HEIGHT: f32 = 4.0;
+pub const STATUS_DURATION: f32 = 3.0;
+
+// how much mana a spell costs in total is its power times this
+const MANA_PER_POWER: f32 = 0.5;
+const STATUS_POWER: f32 = 5.0;
+// restoring mana has to cost more than it gives back
+const MANA_POWER: f32 = 5.0;
+const VOXELS_PER_POWER: f32 = 8.0;
+
+const ELEMENTS: [(MagicElement, &str); 10] = [
+    (MagicElement::NonElemental, "neutral"),
+    (MagicElement::Earth, "earth"),
+    (MagicElement::Electricity, "electricity"),
+    (MagicElement::Water, "water"),
+    (MagicElement::Magnetism, "magnetism"),
+    (MagicElement::Ice, "ice"),
+    (MagicElement::Metal, "metal"),
+    (MagicElement::Plant, "plant"),
+    (MagicElement::Lava, "lava"),
+    (MagicElement::Fire, "fire"),
+];
+
+fn element_keyword(element: MagicElement) -> &'static str {
+    ELEMENTS.iter().find(|(e, _)| *e == element).unwrap().1
+}
+
+fn element_from_keyword(word: &str) -> Option<MagicElement> {
+    ELEMENTS.iter().find(|(_, w)| *w == word).map(|(e, _)| *e)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Shape {
+    // a projectile
+    Bolt,
+    // a short range blast at whatever's in front of the caster
+    Cone,
+    // an area around a point
+    Sphere,
+    // a column raised at a point
+    Wall,
+    // the caster themselves
+    Aura,
+}
+
+impl Shape {
+    const ALL: [Shape; 5] =
+        [Shape::Bolt, Shape::Cone, Shape::Sphere, Shape::Wall, Shape::Aura];
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Shape::Bolt => "bolt",
+            Shape::Cone => "cone",
+            Shape::Sphere => "sphere",
+            Shape::Wall => "wall",
+            Shape::Aura => "aura",
+        }
+    }
+
+    fn base_cast_time(self) -> f32 {
+        match self {
+            Shape::Bolt => 0.3,
+            Shape::Cone => 0.2,
+            Shape::Sphere | Shape::Wall => 0.8,
+            Shape::Aura => 0.5,
+        }
+    }
+
+    // whether a rune makes sense on this shape
+    pub fn allows(self, rune: Rune) -> bool {
+        use Action::*;
+        match (rune.trigger, self, rune.action) {
+            (Trigger::Cast, _, Heal(_) | Mana(_)) => true,
+            (Trigger::Hit, Shape::Bolt, Damage(_) | Status) => true,
+            (Trigger::Hit, Shape::Cone,
+                Damage(_) | Heal(_) | Mana(_) | Status) => true,
+            (Trigger::Hit, Shape::Sphere, Carve | Raise) => true,
+            (Trigger::Hit, Shape::Wall, Raise) => true,
+            (Trigger::Hit, Shape::Aura, Heal(_) | Mana(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModifierKind {
+    Speed,
+    Size,
+    Duration,
+}
+
+impl ModifierKind {
+    const ALL: [ModifierKind; 3] =
+        [ModifierKind::Speed, ModifierKind::Size, ModifierKind::Duration];
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ModifierKind::Speed => "speed",
+            ModifierKind::Size => "size",
+            ModifierKind::Duration => "duration",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Modifier {
+    pub kind: ModifierKind,
+    pub amount: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Trigger {
+    // whatever the spell hits
+    Hit,
+    // the caster, as the spell goes off
+    Cast,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Damage(u32),
+    Heal(u32),
+    Mana(u32),
+    // whatever status the spell's element inflicts
+    Status,
+    Carve,
+    Raise,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rune {
+    pub trigger: Trigger,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpellRunes {
+    pub name: String,
+    pub shape: Shape,
+    // None takes on the caster's primary element
+    pub element: Option<MagicElement>,
+    pub modifiers: Vec<Modifier>,
+    pub runes: Vec<Rune>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuneError {
+    UnexpectedEnd { expected: &'static str },
+    Unexpected { column: usize, found: String, expected: &'static str },
+    BadNumber { column: usize, found: String },
+    OutOfRange { column: usize, modifier: ModifierKind, amount: f32 },
+    Duplicate { column: usize, modifier: ModifierKind },
+    NotAllowed { shape: Shape, rune: Rune },
+    NoStatus(MagicElement),
+    TooMuchDamage,
+    DoesNothing,
+}
+
+impl fmt::Display for RuneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuneError::UnexpectedEnd { expected } =>
+                write!(f, "expected {} but the spell ended", expected),
+            RuneError::Unexpected { column, found, expected } =>
+                write!(f, "column {}: expected {}, found `{}`",
+                    column, expected, found),
+            RuneError::BadNumber { column, found } =>
+                write!(f, "column {}: `{}` isn't a valid number",
+                    column, found),
+            RuneError::OutOfRange { column, modifier, amount } =>
+                write!(f, "column {}: {} {} is outside {}..={}", column,
+                    modifier.keyword(), amount, MIN_MODIFIER, MAX_MODIFIER),
+            RuneError::Duplicate { column, modifier } =>
+                write!(f, "column {}: {} is given more than once",
+                    column, modifier.keyword()),
+            RuneError::NotAllowed { shape, rune } =>
+                write!(f, "`{}` doesn't work on a {}", rune, shape.keyword()),
+            RuneError::NoStatus(element) =>
+                write!(f, "{} spells have no status to inflict",
+                    element_keyword(*element)),
+            RuneError::TooMuchDamage =>
+                write!(f, "the bolt's damage adds up to more than {}",
+                    u32::MAX),
+            RuneError::DoesNothing => write!(f, "the spell has no runes"),
+        }
+    }
+}
+
+impl std::error::Error for RuneError {}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Parser<'a> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in src.char_indices() {
+            if c.is_whitespace() || c == ':' {
+                if let Some(s) = start.take() {
+                    tokens.push((s, &src[s..i]));
+                }
+                if c == ':' {
+                    tokens.push((i, &src[i..i + 1]));
+                }
+            } else if start.is_none() {
+                start = Some(i);
+            }
+        }
+        if let Some(s) = start {
+            tokens.push((s, &src[s..]));
+        }
+
+        Parser { src, tokens, next: 0 }
+    }
+
+    fn column(&self, offset: usize) -> usize {
+        self.src[..offset].chars().count() + 1
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).map(|&(_, t)| t)
+    }
+
+    fn take(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<(usize, &'a str), RuneError> {
+        let &(offset, token) = self.tokens.get(self.next)
+            .ok_or(RuneError::UnexpectedEnd { expected })?;
+        self.next += 1;
+        Ok((self.column(offset), token))
+    }
+
+    fn number<T: FromStr>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<(usize, T), RuneError> {
+        let (column, token) = self.take(expected)?;
+        token.parse()
+            .map(|n| (column, n))
+            .map_err(|_| RuneError::BadNumber {
+                column,
+                found: token.to_owned(),
+            })
+    }
+
+    fn unexpected(
+        column: usize,
+        found: &str,
+        expected: &'static str,
+    ) -> RuneError {
+        RuneError::Unexpected { column, found: found.to_owned(), expected }
+    }
+
+    fn spell(&mut self) -> Result<SpellRunes, RuneError> {
+        let (column, name) = self.take("a spell name")?;
+        let valid = name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(Self::unexpected(column, name, "a spell name"));
+        }
+
+        let (column, colon) = self.take("`:`")?;
+        if colon != ":" {
+            return Err(Self::unexpected(column, colon, "`:`"));
+        }
+
+        const SHAPES: &str = "a shape (bolt, cone, sphere, wall or aura)";
+        let (column, shape) = self.take(SHAPES)?;
+        let Some(shape) = Shape::ALL.into_iter().find(|s| s.keyword() == shape)
+        else {
+            return Err(Self::unexpected(column, shape, SHAPES));
+        };
+
+        let element = self.peek().and_then(element_from_keyword);
+        if element.is_some() {
+            self.next += 1;
+        }
+
+        let mut modifiers: Vec<Modifier> = Vec::new();
+        let mut runes = Vec::new();
+        while let Some(word) = self.peek() {
+            if let Some(kind) = ModifierKind::ALL.into_iter()
+                .find(|m| m.keyword() == word)
+            {
+                let (column, _) = self.take("a modifier")?;
+                if modifiers.iter().any(|m| m.kind == kind) {
+                    return Err(RuneError::Duplicate { column, modifier: kind });
+                }
+
+                let (column, amount) = self.number::<f32>("a number")?;
+                if !(MIN_MODIFIER..=MAX_MODIFIER).contains(&amount) {
+                    return Err(RuneError::OutOfRange {
+                        column,
+                        modifier: kind,
+                        amount,
+                    });
+                }
+
+                modifiers.push(Modifier { kind, amount });
+                continue;
+            }
+
+            let (column, word) = self.take("a modifier or `on`")?;
+            if word != "on" {
+                return Err(
+                    Self::unexpected(column, word, "a modifier or `on`"));
+            }
+            runes.push(self.rune()?);
+        }
+
+        Ok(SpellRunes {
+            name: name.to_owned(),
+            shape,
+            element,
+            modifiers,
+            runes,
+        })
+    }
+
+    fn rune(&mut self) -> Result<Rune, RuneError> {
+        const TRIGGERS: &str = "a trigger (hit or cast)";
+        let (column, trigger) = self.take(TRIGGERS)?;
+        let trigger = match trigger {
+            "hit" => Trigger::Hit,
+            "cast" => Trigger::Cast,
+            _ => return Err(Self::unexpected(column, trigger, TRIGGERS)),
+        };
+
+        const ACTIONS: &str =
+            "an action (damage, heal, mana, status, carve or raise)";
+        let (column, action) = self.take(ACTIONS)?;
+        let action = match action {
+            "damage" => Action::Damage(self.number("an amount")?.1),
+            "heal" => Action::Heal(self.number("an amount")?.1),
+            "mana" => Action::Mana(self.number("an amount")?.1),
+            "status" => Action::Status,
+            "carve" => Action::Carve,
+            "raise" => Action::Raise,
+            _ => return Err(Self::unexpected(column, action, ACTIONS)),
+        };
+
+        Ok(Rune { trigger, action })
+    }
+}
+
+impl SpellRunes {
+    pub fn parse(src: &str) -> Result<SpellRunes, RuneError> {
+        Parser::new(src).spell()
+    }
+
+    pub fn modifier(&self, kind: ModifierKind) -> f32 {
+        self.modifiers.iter()
+            .find(|m| m.kind == kind)
+            .map(|m| m.amount)
+            .unwrap_or(1.0)
+    }
+
+    fn terrain_shape(&self) -> VoxelShape {
+        let size = self.modifier(ModifierKind::Size);
+        match self.shape {
+            Shape::Wall => VoxelShape::Column {
+                radius: WALL_RADIUS * size,
+                height: (WALL_HEIGHT * self.modifier(ModifierKind::Duration))
+                    .round()
+                    .max(1.0) as i32,
+            },
+            _ => VoxelShape::Sphere { radius: SPHERE_RADIUS * size },
+        }
+    }
+
+    // type checks the runes and works out what the spell does and costs
+    pub fn compile(&self) -> Result<Spell, RuneError> {
+        if self.runes.is_empty() {
+            return Err(RuneError::DoesNothing);
+        }
+
+        let speed = self.modifier(ModifierKind::Speed);
+        let size = self.modifier(ModifierKind::Size);
+        let duration = self.modifier(ModifierKind::Duration);
+
+        let mut power = 0.0;
+        let mut bolt_damage: u32 = 0;
+        let mut effects = Vec::new();
+        for &rune in self.runes.iter() {
+            if !self.shape.allows(rune) {
+                return Err(RuneError::NotAllowed { shape: self.shape, rune });
+            }
+
+            let effect = match rune.action {
+                Action::Damage(amount) => {
+                    power += amount as f32;
+                    // bolts deal their damage through the projectile
+                    if self.shape == Shape::Bolt {
+                        bolt_damage = bolt_damage.checked_add(amount)
+                            .ok_or(RuneError::TooMuchDamage)?;
+                        continue;
+                    }
+                    SpellEffect::Damage { amount }
+                }
+                Action::Heal(amount) => {
+                    power += amount as f32;
+                    SpellEffect::Heal { amount }
+                }
+                Action::Mana(amount) => {
+                    power += amount as f32 * MANA_POWER;
+                    SpellEffect::RestoreMana { amount }
+                }
+                Action::Status => {
+                    if let Some(element) = self.element {
+                        if StatusKind::for_element(element).is_none() {
+                            return Err(RuneError::NoStatus(element));
+                        }
+                    }
+                    let duration = STATUS_DURATION * duration;
+                    power += duration * STATUS_POWER;
+                    SpellEffect::Status { kind: None, duration }
+                }
+                Action::Carve | Action::Raise => {
+                    let shape = self.terrain_shape();
+                    power += shape.offsets().len() as f32 / VOXELS_PER_POWER;
+                    SpellEffect::Terraform {
+                        shape,
+                        carve: rune.action == Action::Carve,
+                    }
+                }
+            };
+
+            effects.push(match rune.trigger {
+                Trigger::Hit => effect,
+                Trigger::Cast => SpellEffect::OnCaster(Box::new(effect)),
+            });
+        }
+
+        let targeting = match self.shape {
+            Shape::Bolt => Targeting::Direction,
+            Shape::Cone => Targeting::Entity { range: CONE_RANGE * size },
+            Shape::Sphere | Shape::Wall =>
+                Targeting::Point { range: POINT_RANGE },
+            Shape::Aura => Targeting::Caster,
+        };
+
+        if self.shape == Shape::Bolt {
+            power *= speed;
+            effects.insert(0, SpellEffect::Projectile {
+                kind: None,
+                speed: BOLT_SPEED * speed,
+                damage: bolt_damage,
+                radius: BOLT_RADIUS * size,
+                lifetime: BOLT_LIFETIME * duration,
+            });
+        }
+
+        let cast_time = self.shape.base_cast_time() * size;
+        Ok(Spell {
+            name: self.name.clone(),
+            element: self.element,
+            cost: self.cost(power),
+            cast_time,
+            cooldown: cast_time * 3.0,
+            targeting,
+            effects,
+        })
+    }
+
+    // spells of an element cost its colours, split evenly. spells that take
+    // on the caster's element cost black mana, which any caster can pay
+    fn cost(&self, power: f32) -> ManaCost {
+        let total = ((power * MANA_PER_POWER).ceil() as u32).max(1);
+        let mut cost = ManaCost::default();
+        match self.element.map(|e| e.colors()) {
+            None => cost.black = total,
+            Some((a, b)) if a == b => *cost.get_mut(a) = total,
+            Some((a, b)) => {
+                *cost.get_mut(a) = total - total / 2;
+                *cost.get_mut(b) = total / 2;
+            }
+        }
+        cost
+    }
+}
+
+// parses and compiles in one go
+pub fn parse_spell(src: &str) -> Result<Spell, RuneError> {
+    SpellRunes::parse(src)?.compile()
+}
+
+impl FromStr for SpellRunes {
+    type Err = RuneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SpellRunes::parse(s)
+    }
+}
+
+impl fmt::Display for Rune {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trigger = match self.trigger {
+            Trigger::Hit => "hit",
+            Trigger::Cast => "cast",
+        };
+        write!(f, "on {} ", trigger)?;
+        match self.action {
+            Action::Damage(n) => write!(f, "damage {}", n),
+            Action::Heal(n) => write!(f, "heal {}", n),
+            Action::Mana(n) => write!(f, "mana {}", n),
+            Action::Status => write!(f, "status"),
+            Action::Carve => write!(f, "carve"),
+            Action::Raise => write!(f, "raise"),
+        }
+    }
+}
+
+// writes the runes back out in a form `parse` reads back the same
+impl fmt::Display for SpellRunes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.shape.keyword())?;
+        if let Some(element) = self.element {
+            write!(f, " {}", element_keyword(element))?;
+        }
+        for m in self.modifiers.iter() {
+            write!(f, " {} {}", m.kind.keyword(), m.amount)?;
+        }
+        for rune in self.runes.iter() {
+            write!(f, " {}", rune)?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,326 @@
-// TODO: do this
-pub enum Spell {
-    
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use bevy::utils::HashMap;
+
+use crate::*;
+use hash::StableHasher;
+use magic::{ManaColor, MagicElement};
+use magic::assets::{SpellBook, DEFAULT_SPELLS};
+use magic::components::MagicCaster;
+use magic::magnet::{Falloff, MagnetMode};
+use magic::projectile::ProjectileKind;
+use magic::status::StatusKind;
+use voxel::VOXEL_SIZE;
+use voxel::edit::{VoxelShape, MAX_SHAPE_RADIUS};
+
+// colours left out of a spell file cost nothing
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManaCost {
+    pub black: u32,
+    pub red: u32,
+    pub yellow: u32,
+    pub blue: u32,
+}
+
+impl ManaCost {
+    pub fn get(&self, color: ManaColor) -> u32 {
+        match color {
+            ManaColor::Black => self.black,
+            ManaColor::Red => self.red,
+            ManaColor::Yellow => self.yellow,
+            ManaColor::Blue => self.blue,
+        }
+    }
+
+    pub fn get_mut(&mut self, color: ManaColor) -> &mut u32 {
+        match color {
+            ManaColor::Black => &mut self.black,
+            ManaColor::Red => &mut self.red,
+            ManaColor::Yellow => &mut self.yellow,
+            ManaColor::Blue => &mut self.blue,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.black + self.red + self.yellow + self.blue
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Targeting {
+    // the caster themselves
+    Caster,
+    // another entity within range
+    Entity { range: f32 },
+    // a point in the world within range
+    Point { range: f32 },
+    // wherever the caster is looking
+    Direction,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpellEffect {
+    Damage { amount: u32 },
+    Heal { amount: u32 },
+    RestoreMana { amount: u32 },
+    Overcharge { amount: u32 },
+    // launches a projectile; the kind defaults to the spell element's
+    Projectile {
+        kind: Option<ProjectileKind>,
+        speed: f32,
+        damage: u32,
+        radius: f32,
+        lifetime: f32,
+    },
+    // reshapes the voxels around the target, depending on the element
+    Terraform { shape: VoxelShape, carve: bool },
+    // inflicts a status on whatever the spell hits; the kind defaults to the
+    // spell element's, if it has one
+    Status { kind: Option<StatusKind>, duration: f32 },
+    // a magnetic field around the target that moves metal about
+    Magnet {
+        mode: MagnetMode,
+        strength: f32,
+        radius: f32,
+        falloff: Falloff,
+        duration: f32,
+    },
+    // blows up at the target, or where the spell's projectile lands
+    Explosion { radius: f32, power: f32 },
+    // applies the effect to the caster instead of the target
+    OnCaster(Box<SpellEffect>),
+}
+
+impl SpellEffect {
+    pub fn status(&self, element: MagicElement) -> Option<(StatusKind, f32)> {
+        let SpellEffect::Status { kind, duration } = *self
+        else {
+            return None;
+        };
+        Some((kind.or(StatusKind::for_element(element))?, duration))
+    }
+
+    // the radius and power of an explosion effect
+    pub fn explosion(&self) -> Option<(f32, f32)> {
+        let SpellEffect::Explosion { radius, power } = *self
+        else {
+            return None;
+        };
+        Some((radius, power))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spell {
+    pub name: String,
+    // None takes on the caster's primary element
+    pub element: Option<MagicElement>,
+    pub cost: ManaCost,
+    // seconds
+    pub cast_time: f32,
+    pub cooldown: f32,
+    pub targeting: Targeting,
+    pub effects: Vec<SpellEffect>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpellError {
+    NoName,
+    NoCost,
+    NoEffects,
+    BadTiming,
+    BadTargeting,
+    BadEffect(&'static str),
+}
+
+impl fmt::Display for SpellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpellError::NoName => write!(f, "the spell has no name"),
+            SpellError::NoCost => write!(f, "the spell costs no mana"),
+            SpellError::NoEffects => write!(f, "the spell has no effects"),
+            SpellError::BadTiming =>
+                write!(f, "cast times and cooldowns can't be negative"),
+            SpellError::BadTargeting =>
+                write!(f, "targeting ranges have to be positive"),
+            SpellError::BadEffect(why) => write!(f, "bad effect: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for SpellError {}
+
+// false for nan too, which a plain `<= 0.0` would let through
+fn positive(x: f32) -> bool {
+    x > 0.0
+}
+
+// magnets and explosions walk the voxels in their radius like terraform
+// shapes do, so they get the same limit
+fn within_shape(radius: f32) -> bool {
+    positive(radius) && radius <= MAX_SHAPE_RADIUS * VOXEL_SIZE
+}
+
+impl SpellEffect {
+    fn validate(&self) -> Result<(), SpellError> {
+        match *self {
+            SpellEffect::Damage { amount: 0 }
+            | SpellEffect::Heal { amount: 0 } => {
+                return Err(SpellError::BadEffect(
+                    "damage and healing can't be zero"));
+            }
+            SpellEffect::Projectile { speed, radius, lifetime, .. } => {
+                let ok = positive(speed) && positive(radius)
+                    && positive(lifetime);
+                if !ok {
+                    return Err(SpellError::BadEffect("projectiles need a \
+                        positive speed, radius and lifetime"));
+                }
+            }
+            SpellEffect::Status { duration, .. } => {
+                if !positive(duration) {
+                    return Err(SpellError::BadEffect(
+                        "statuses need a positive duration"));
+                }
+            }
+            SpellEffect::Magnet { strength, radius, duration, .. } => {
+                let ok = positive(strength) && within_shape(radius)
+                    && positive(duration);
+                if !ok {
+                    return Err(SpellError::BadEffect("magnets need a \
+                        positive strength, radius and duration, and can't \
+                        be too big"));
+                }
+            }
+            SpellEffect::Explosion { radius, power } => {
+                if !within_shape(radius) || !positive(power) {
+                    return Err(SpellError::BadEffect("explosions need a \
+                        positive radius and power, and can't be too big"));
+                }
+            }
+            SpellEffect::Terraform { shape, .. } => {
+                if !shape.in_bounds() {
+                    return Err(SpellError::BadEffect(
+                        "terraform shapes can't be negative or too big"));
+                }
+            }
+            SpellEffect::OnCaster(ref inner) => {
+                if matches!(**inner, SpellEffect::OnCaster(_)) {
+                    return Err(SpellError::BadEffect(
+                        "OnCaster can't wrap another OnCaster"));
+                }
+                inner.validate()?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Spell {
+    pub fn resolve_element(&self, caster: &MagicCaster) -> MagicElement {
+        self.element.unwrap_or(caster.primary)
+    }
+
+    // catches what the types can't, for spells loaded from files
+    pub fn validate(&self) -> Result<(), SpellError> {
+        if self.name.is_empty() {
+            return Err(SpellError::NoName);
+        }
+        if self.cost.total() == 0 {
+            return Err(SpellError::NoCost);
+        }
+        if self.effects.is_empty() {
+            return Err(SpellError::NoEffects);
+        }
+        // written this way round so nan fails too
+        let timing = self.cast_time >= 0.0 && self.cooldown >= 0.0;
+        if !timing {
+            return Err(SpellError::BadTiming);
+        }
+
+        match self.targeting {
+            Targeting::Entity { range } | Targeting::Point { range }
+                if !positive(range) => return Err(SpellError::BadTargeting),
+            _ => (),
+        }
+
+        self.effects.iter().try_for_each(|e| e.validate())
+    }
+}
+
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SpellId(u32);
+
+impl SpellId {
+    pub fn spell(self, spells: &Spells) -> &Spell {
+        &spells.spells[self.0 as usize]
+    }
+
+    pub fn id(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Spells {
+    spells: Vec<Spell>,
+    spell_names: HashMap<String, SpellId>,
+}
+
+impl Spells {
+    pub fn get(&self, id: SpellId) -> Option<&Spell> {
+        self.spells.get(id.0 as usize)
+    }
+
+    pub fn id_from_name(&self, name: &str) -> Option<SpellId> {
+        self.spell_names.get(name).cloned()
+    }
+
+    pub fn add_spell(&mut self, spell: Spell) -> SpellId {
+        let id = SpellId(self.spells.len() as u32);
+        self.spell_names.insert(spell.name.clone(), id);
+        self.spells.push(spell);
+        id
+    }
+
+    // replaces the spell with the same name, keeping its id, or adds it.
+    // ids are handed out to casters, so spells are never taken away
+    pub fn upsert(&mut self, spell: Spell) -> SpellId {
+        match self.id_from_name(&spell.name) {
+            Some(id) => {
+                self.spells[id.0 as usize] = spell;
+                id
+            }
+            None => self.add_spell(spell),
+        }
+    }
+
+    // covers every field of every spell, since a spell that's been
+    // rebalanced on only one side is as bad as a missing one
+    pub fn registry_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        for spell in self.spells.iter() {
+            ron::to_string(spell).unwrap_or_default().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SpellId, &Spell)> {
+        self.spells.iter()
+            .enumerate()
+            .map(|(i, s)| (SpellId(i as u32), s))
+    }
+}
+
+// the built in spells are the shipped book, so they match it exactly
+// before the asset has loaded
+pub fn register_default_spells(spells: &mut Spells) {
+    let book = SpellBook::from_ron(DEFAULT_SPELLS)
+        .expect("the built in spell book is invalid");
+    for spell in book.spells {
+        spells.add_spell(spell);
+    }
 }
@@ -0,0 +1,236 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::InterruptCast;
+use magic::damage::{DamageEvent, Dead};
+
+// seconds between damage ticks from burning
+pub const BURN_INTERVAL: f32 = 1.0;
+pub const BURN_DAMAGE: u32 = 3;
+
+pub const MAX_BURN_STACKS: u8 = 3;
+pub const MAX_MAGNET_STACKS: u8 = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusKind {
+    // takes fire damage over time, stacks
+    Burning,
+    // moves at half speed
+    Frozen,
+    // can't move or cast
+    Shocked,
+    // can't move, but can still cast
+    Rooted,
+    // pulled around by magnetism, stacks
+    Magnetized,
+}
+
+impl StatusKind {
+    // what an element's spells inflict, if anything
+    pub fn for_element(element: MagicElement) -> Option<StatusKind> {
+        match element {
+            MagicElement::Fire | MagicElement::Lava => Some(StatusKind::Burning),
+            MagicElement::Ice => Some(StatusKind::Frozen),
+            MagicElement::Electricity => Some(StatusKind::Shocked),
+            MagicElement::Plant => Some(StatusKind::Rooted),
+            MagicElement::Magnetism
+            | MagicElement::Metal => Some(StatusKind::Magnetized),
+
+            MagicElement::NonElemental
+            | MagicElement::Earth
+            | MagicElement::Water => None,
+        }
+    }
+
+    pub fn max_stacks(self) -> u8 {
+        match self {
+            StatusKind::Burning => MAX_BURN_STACKS,
+            StatusKind::Magnetized => MAX_MAGNET_STACKS,
+            _ => 1,
+        }
+    }
+
+    // the status this one puts an end to, like fire thawing ice
+    fn cancels(self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Burning => Some(StatusKind::Frozen),
+            StatusKind::Frozen => Some(StatusKind::Burning),
+            _ => None,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            StatusKind::Burning => MagicElement::Fire.color(),
+            StatusKind::Frozen => MagicElement::Ice.color(),
+            StatusKind::Shocked => MagicElement::Electricity.color(),
+            StatusKind::Rooted => MagicElement::Plant.color(),
+            StatusKind::Magnetized => MagicElement::Magnetism.color(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub remaining: f32,
+    pub stacks: u8,
+    pub source: Option<Entity>,
+    // until the next damage tick
+    next_tick: f32,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    pub fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+        self.0.iter().find(|s| s.kind == kind)
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    // reapplying a status refreshes its duration to the longer of the two
+    // and adds a stack, up to the kind's limit
+    pub fn apply(
+        &mut self,
+        kind: StatusKind,
+        duration: f32,
+        source: Option<Entity>,
+    ) {
+        if let Some(cancelled) = kind.cancels() {
+            if self.has(cancelled) {
+                self.0.retain(|s| s.kind != cancelled);
+                return;
+            }
+        }
+
+        match self.0.iter_mut().find(|s| s.kind == kind) {
+            Some(status) => {
+                status.remaining = status.remaining.max(duration);
+                status.stacks = (status.stacks + 1).min(kind.max_stacks());
+                status.source = source.or(status.source);
+            }
+            None => self.0.push(StatusEffect {
+                kind,
+                remaining: duration,
+                stacks: 1,
+                source,
+                next_tick: BURN_INTERVAL,
+            }),
+        }
+    }
+
+    pub fn can_move(&self) -> bool {
+        !self.has(StatusKind::Shocked) && !self.has(StatusKind::Rooted)
+    }
+
+    pub fn can_cast(&self) -> bool {
+        !self.has(StatusKind::Shocked)
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if !self.can_move() {
+            return 0.0;
+        }
+
+        let mut speed = 1.0;
+        if self.has(StatusKind::Frozen) {
+            speed *= 0.5;
+        }
+        if let Some(magnet) = self.get(StatusKind::Magnetized) {
+            speed *= 1.0 - 0.1 * magnet.stacks as f32;
+        }
+        speed
+    }
+}
+
+// the kinds of status an entity has, sent to clients so they can show
+// them. kept separate from StatusEffects so timers ticking down don't
+// get replicated every tick
+#[derive(Component, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VisibleStatuses(pub Vec<StatusKind>);
+
+#[derive(Event, Clone, Debug)]
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub kind: StatusKind,
+    pub duration: f32,
+    pub source: Option<Entity>,
+}
+
+pub(crate) fn apply_statuses(
+    mut commands: Commands,
+    mut applied: EventReader<ApplyStatus>,
+    mut interrupts: EventWriter<InterruptCast>,
+    mut targets: Query<Option<&mut StatusEffects>, Without<Dead>>,
+) {
+    for a in applied.read() {
+        let Ok(statuses) = targets.get_mut(a.target)
+        else {
+            continue;
+        };
+
+        if a.kind == StatusKind::Shocked {
+            interrupts.send(InterruptCast { caster: a.target });
+        }
+
+        match statuses {
+            Some(mut statuses) => statuses.apply(a.kind, a.duration, a.source),
+            None => {
+                let mut statuses = StatusEffects::default();
+                statuses.apply(a.kind, a.duration, a.source);
+                commands.entity(a.target).insert(statuses);
+            }
+        }
+    }
+}
+
+pub(crate) fn tick_statuses(
+    time: Res<Time>,
+    mut targets: Query<(Entity, &mut StatusEffects, Has<Dead>)>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    for (entity, mut statuses, dead) in targets.iter_mut() {
+        if dead {
+            if !statuses.0.is_empty() {
+                statuses.0.clear();
+            }
+            continue;
+        }
+
+        for status in statuses.0.iter_mut() {
+            status.remaining -= time.delta_seconds();
+            if status.kind != StatusKind::Burning {
+                continue;
+            }
+
+            status.next_tick -= time.delta_seconds();
+            if status.next_tick <= 0.0 {
+                status.next_tick += BURN_INTERVAL;
+                damage.send(DamageEvent {
+                    target: entity,
+                    amount: BURN_DAMAGE * status.stacks as u32,
+                    element: MagicElement::Fire,
+                    source: status.source,
+                });
+            }
+        }
+
+        statuses.0.retain(|s| s.remaining > 0.0);
+    }
+}
+
+pub(crate) fn update_visible_statuses(
+    mut commands: Commands,
+    targets: Query<(Entity, &StatusEffects, Option<&VisibleStatuses>),
+        Changed<StatusEffects>>,
+) {
+    for (entity, statuses, visible) in targets.iter() {
+        let kinds = VisibleStatuses(statuses.0.iter().map(|s| s.kind).collect());
+        if visible != Some(&kinds) {
+            commands.entity(entity).insert(kinds);
+        }
+    }
+}
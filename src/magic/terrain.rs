@@ -0,0 +1,195 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::{SpellCast, SpellTarget};
+use magic::components::Health;
+use magic::damage::{DamageEvent, Dead};
+use magic::projectile::ProjectileImpact;
+use magic::spells::{SpellEffect, Spells};
+use voxel::{world_to_voxel, VoxelRes, Voxels, VOXEL_SIZE};
+use voxel::edit::{ProtectedRegions, VoxelShape};
+use voxel::sim::VoxelSim;
+
+// damage dealt each simulation step to anything touching charged voxels
+pub const SHOCK_DAMAGE: u32 = 4;
+
+// how many voxels down from an entity's centre count as touching it
+pub const SHOCK_REACH: i32 = 5;
+
+// which voxels an element turns into which, by name. `carve` picks the
+// destructive half for elements that have one
+pub fn terrain_rules(
+    element: MagicElement,
+    carve: bool,
+) -> &'static [(&'static str, &'static str)] {
+    match (element, carve) {
+        (MagicElement::Earth, false) => &[
+            ("air", "stone"),
+            ("water", "stone"),
+        ],
+        (MagicElement::Earth, true) => &[
+            ("solid", "air"),
+            ("stone", "air"),
+            ("plant", "air"),
+        ],
+        (MagicElement::Ice, _) => &[
+            ("water", "ice"),
+        ],
+        // the reaction simulation spreads the fire from there
+        (MagicElement::Fire, _) => &[
+            ("plant", "fire"),
+        ],
+        (MagicElement::Lava, _) => &[
+            ("solid", "lava"),
+            ("stone", "lava"),
+        ],
+        (MagicElement::Metal, false) => &[
+            ("air", "metal"),
+        ],
+        (MagicElement::Metal, true) => &[
+            ("metal", "air"),
+        ],
+        _ => &[],
+    }
+}
+
+// applies one terraform around `center`, returning how many voxels changed
+pub fn terraform(
+    voxels: &mut Voxels,
+    protected: &ProtectedRegions,
+    element: MagicElement,
+    carve: bool,
+    shape: VoxelShape,
+    center: IVec3,
+) -> usize {
+    let rules: Vec<_> = terrain_rules(element, carve).iter()
+        .filter_map(|&(from, to)| Some((
+            voxels.id_from_name(from)?,
+            voxels.id_from_name(to)?,
+        )))
+        .collect();
+    if rules.is_empty() {
+        return 0;
+    }
+
+    let mut changed = 0;
+    for off in shape.offsets() {
+        let pos = center + off;
+        if protected.is_protected(pos) {
+            continue;
+        }
+
+        let current = voxels.get_block(pos.x, pos.y, pos.z);
+        let Some(&(_, to)) = rules.iter().find(|(from, _)| *from == current)
+        else {
+            continue;
+        };
+
+        if voxels.edit_block(pos.x, pos.y, pos.z, to) {
+            changed += 1;
+        }
+    }
+    changed
+}
+
+pub(crate) fn apply_terrain_effects(
+    spells: Res<Spells>,
+    voxels: Res<VoxelRes>,
+    protected: Res<ProtectedRegions>,
+    mut cast: EventReader<SpellCast>,
+    positions: Query<&GlobalTransform>,
+) {
+    for c in cast.read() {
+        let spell = c.spell.spell(&spells);
+        if !spell.effects.iter()
+            .any(|e| matches!(e, SpellEffect::Terraform { .. }))
+        {
+            continue;
+        }
+
+        let Ok(from) = positions.get(c.caster).map(|t| t.translation())
+        else {
+            continue;
+        };
+
+        let point = match c.target {
+            SpellTarget::Caster => from,
+            SpellTarget::Point(point) => point,
+            SpellTarget::Entity(e) => {
+                let Ok(to) = positions.get(e)
+                else {
+                    continue;
+                };
+                to.translation()
+            }
+            SpellTarget::Direction(_) => continue,
+        };
+
+        // points land on the surface of a voxel, so nudge them half a voxel
+        // into the ground to carve or out of it to build
+        let dir = (point - from).normalize_or_zero();
+
+        let Ok(mut voxels) = voxels.write()
+        else {
+            return;
+        };
+
+        for effect in spell.effects.iter() {
+            let SpellEffect::Terraform { shape, carve } = *effect
+            else {
+                continue;
+            };
+
+            let nudge = (if carve { 0.5 } else { -0.5 }) * VOXEL_SIZE;
+            let center = world_to_voxel(point + dir * nudge);
+            terraform(
+                &mut voxels, &protected, c.element, carve, shape, center);
+        }
+    }
+}
+
+// electric projectiles charge any water or metal they land in or next to
+pub(crate) fn electrify_terrain(
+    voxels: Res<VoxelRes>,
+    mut sim: ResMut<VoxelSim>,
+    mut impacts: EventReader<ProjectileImpact>,
+) {
+    for impact in impacts.read() {
+        if impact.element != MagicElement::Electricity {
+            continue;
+        }
+
+        let Ok(voxels) = voxels.read()
+        else {
+            return;
+        };
+
+        let at = impact.voxel.unwrap_or(world_to_voxel(impact.position));
+        sim.conduct(&voxels, at);
+    }
+}
+
+pub(crate) fn shock_charged_entities(
+    sim: Res<VoxelSim>,
+    mut last_ticks: Local<u64>,
+    targets: Query<(Entity, &GlobalTransform), (With<Health>, Without<Dead>)>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    if sim.ticks == *last_ticks {
+        return;
+    }
+    *last_ticks = sim.ticks;
+
+    for (entity, trans) in targets.iter() {
+        let centre = world_to_voxel(trans.translation());
+        let touching = (0..=SHOCK_REACH)
+            .any(|d| sim.is_charged(centre - IVec3::Y * d));
+        if touching {
+            damage.send(DamageEvent {
+                target: entity,
+                amount: SHOCK_DAMAGE,
+                element: MagicElement::Electricity,
+                source: None,
+            });
+        }
+    }
+}
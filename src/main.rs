@@ -1,16 +1,63 @@
+use std::path::PathBuf;
+
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
 // use bevy_rapier3d::render::{
 //     DebugRenderMode, DebugRenderStyle, RapierDebugRenderPlugin};
 // use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use clap::Parser;
 use magic_game::*;
+use client_plugin::input::{InputSettings, DEFAULT_INPUT_PATH};
+use client_plugin::settings::{SettingsPaths, VideoSettings};
+use client_plugin::settings::DEFAULT_VIDEO_PATH;
+use net::{host, NetArgs, NetSettings, NetSide};
+use npc::NpcPlugin;
 
 mod fps;
 use fps::*;
 
+mod version_overlay;
+use version_overlay::*;
+
+#[derive(Parser)]
+#[command(version = version::LONG_VERSION_STR)]
+struct Args {
+    /// Host a session that other players can join
+    #[arg(long)]
+    host: bool,
+
+    /// Key bindings and mouse settings; missing files fall back to the
+    /// defaults
+    #[arg(long, default_value = DEFAULT_INPUT_PATH)]
+    input: PathBuf,
+
+    /// Render distance, field of view and the like; missing files fall
+    /// back to the defaults
+    #[arg(long, default_value = DEFAULT_VIDEO_PATH)]
+    video: PathBuf,
+
+    #[command(flatten)]
+    net: NetArgs,
+}
+
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let args = Args::parse();
+    let input = InputSettings::load(&args.input).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let video = VideoSettings::load(&args.video).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut app = App::new();
+    app
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            // spell files and the like reload as they're edited
+            watch_for_changes_override: Some(cfg!(debug_assertions)),
+            ..default()
+        }))
         // .add_plugins(WorldInspectorPlugin::new())
         .add_plugins(FrameTimeDiagnosticsPlugin::default())
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
@@ -20,7 +67,29 @@ fn main() {
         //     mode: DebugRenderMode::all(),
         // })
         .add_plugins(GamePlugin)
-        .add_systems(Startup, setup_fps_counter)
-        .add_systems(Update, fps_text_update_system)
-        .run();
+        // this is always singleplayer or the host, so the npcs live here
+        .add_plugins(NpcPlugin)
+        .insert_resource(input)
+        .insert_resource(video)
+        .insert_resource(SettingsPaths {
+            input: args.input.clone(),
+            video: args.video.clone(),
+        })
+        .add_systems(Startup, (setup_fps_counter, setup_version_overlay))
+        .add_systems(Update, (fps_text_update_system, show_fps_counter));
+
+    if args.host {
+        let settings = NetSettings::from_args(&args.net, NetSide::Server)
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+
+        app
+            .add_plugins(host::host_plugins(&settings))
+            .add_plugins(host::HostPlugin)
+            .insert_resource(settings);
+    }
+
+    app.run();
 }
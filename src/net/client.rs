@@ -1,24 +1,34 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-
 use crate::*;
 use client::*;
-use super::server::ADDR as SERVER_ADDR;
-use super::protocol::PROTOCOL_ID;
-
-pub const ADDR: SocketAddr =
-    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 6942);
+use super::config::NetSettings;
+use super::handshake::{
+    DisconnectReason, HandshakeChannel, Hello, LoadingRegistries, Registries,
+    Welcome,
+};
+use super::protocol::{PlayerId, PROTOCOL_ID};
+use crate::version::{VERSION, VERSION_STR};
 
 pub fn client_plugin(
     shared: SharedConfig,
+    settings: &NetSettings,
+) -> ClientPlugins {
+    let io = IoConfig::from_transport(
+        ClientTransport::UdpSocket(settings.client.bind));
+    client_plugin_with_io(shared, settings, io)
+}
+
+pub fn client_plugin_with_io(
+    shared: SharedConfig,
+    settings: &NetSettings,
+    io: IoConfig,
 ) -> ClientPlugins {
-    let io = IoConfig::from_transport(ClientTransport::UdpSocket(ADDR));
     let config = ClientConfig {
         shared,
         net: NetConfig::Netcode {
             // TODO: get this somehow
             auth: Authentication::Manual {
-                server_addr: SERVER_ADDR,
-                client_id: 0,
+                server_addr: settings.client.server_addr,
+                client_id: settings.client_id(),
                 private_key: Key::default(),
                 protocol_id: PROTOCOL_ID,
             }, // TODO
@@ -29,4 +39,116 @@ pub fn client_plugin(
     };
 
     ClientPlugins::new(config)
-}
\ No newline at end of file
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LocalClientId(pub ClientId);
+
+#[derive(Component)]
+pub struct LocalPlayer;
+
+pub struct ClientNetPlugin;
+
+impl Plugin for ClientNetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            on_connect,
+            send_hello,
+            on_welcome,
+            on_rejected,
+            claim_local_player,
+        ).chain());
+    }
+}
+
+// the registries we last told the server about
+#[derive(Resource, Default)]
+struct SentRegistries(Option<Registries>);
+
+fn on_connect(
+    mut commands: Commands,
+    mut connections: EventReader<ConnectEvent>,
+) {
+    for c in connections.read() {
+        commands.insert_resource(LocalClientId(c.client_id()));
+        commands.remove_resource::<DisconnectReason>();
+        commands.remove_resource::<ServerInfo>();
+        commands.insert_resource(SentRegistries::default());
+    }
+}
+
+// the hello waits for registries that are still loading, and goes out again
+// whenever a reload changes them so the server can check them again
+fn send_hello(
+    mut conn: ResMut<ConnectionManager>,
+    sent: Option<ResMut<SentRegistries>>,
+    registries: Res<Registries>,
+    loading: Res<LoadingRegistries>,
+) {
+    let Some(mut sent) = sent
+    else {
+        return;
+    };
+    if !loading.done() || sent.0.as_ref() == Some(&*registries) {
+        return;
+    }
+
+    let hello = Hello {
+        version: VERSION,
+        version_str: VERSION_STR.to_owned(),
+        registries: registries.clone(),
+    };
+    if conn.send_message::<HandshakeChannel, _>(&hello).is_err() {
+        error!("could not send handshake to the server");
+    }
+    sent.0 = Some(registries.clone());
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ServerInfo {
+    pub version_str: String,
+}
+
+fn on_welcome(
+    mut commands: Commands,
+    mut welcomes: EventReader<MessageEvent<Welcome>>,
+) {
+    for w in welcomes.read() {
+        info!("the server is running {}", w.message.version_str);
+        commands.insert_resource(ServerInfo {
+            version_str: w.message.version_str.clone(),
+        });
+    }
+}
+
+// the reason stays around as a resource so the ui can show it after the
+// connection is gone
+fn on_rejected(
+    mut commands: Commands,
+    mut reasons: EventReader<MessageEvent<DisconnectReason>>,
+) {
+    for r in reasons.read() {
+        error!("the server disconnected us: {}", r.message);
+        commands.insert_resource(r.message.clone());
+    }
+}
+
+// looks at every unclaimed player rather than just the `Added` ones, since
+// ours can replicate before we know our own id
+fn claim_local_player(
+    mut commands: Commands,
+    local: Option<Res<LocalClientId>>,
+    players: Query<(Entity, &PlayerId), Without<LocalPlayer>>,
+) {
+    let Some(local) = local
+    else {
+        return;
+    };
+
+    for (entity, player) in players.iter() {
+        if player.0 == local.0 {
+            info!("joined the session as {}", player.0);
+            commands.entity(entity).insert(LocalPlayer);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+
+use crate::*;
+
+pub const DEFAULT_CONFIG_PATH: &str = "net.ron";
+
+pub const DEFAULT_SERVER_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 42069);
+
+// port 0 lets the os pick a free port, so several clients can share a host
+pub const DEFAULT_CLIENT_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+#[derive(Parser, Debug)]
+#[command(version = crate::version::LONG_VERSION_STR)]
+pub struct NetArgs {
+    /// Network config file; missing files fall back to the defaults
+    #[arg(long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    /// Address to bind the local socket to
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+
+    /// Port to bind the local socket to
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Address of the server to connect to
+    #[arg(long)]
+    pub server_addr: Option<SocketAddr>,
+
+    /// Client id to connect with; random if unset
+    #[arg(long)]
+    pub client_id: Option<u64>,
+
+    /// Maximum number of clients the server accepts
+    #[arg(long)]
+    pub max_clients: Option<usize>,
+
+    /// Simulation ticks per second
+    #[arg(long)]
+    pub tick_rate: Option<f64>,
+
+    /// Milliseconds between client packets (0 sends every frame)
+    #[arg(long)]
+    pub client_send_interval: Option<u64>,
+
+    /// Milliseconds between server packets (0 sends every frame)
+    #[arg(long)]
+    pub server_send_interval: Option<u64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetSide {
+    Client,
+    Server,
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct NetSettings {
+    pub server: ServerSettings,
+    pub client: ClientSettings,
+    pub tick_rate: f64,
+    pub client_send_interval_ms: u64,
+    pub server_send_interval_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub bind: SocketAddr,
+    pub max_clients: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClientSettings {
+    pub bind: SocketAddr,
+    pub server_addr: SocketAddr,
+    pub client_id: Option<u64>,
+}
+
+impl Default for NetSettings {
+    fn default() -> Self {
+        NetSettings {
+            server: ServerSettings::default(),
+            client: ClientSettings::default(),
+            tick_rate: 64.0,
+            client_send_interval_ms: 0,
+            server_send_interval_ms: 40,
+        }
+    }
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            bind: DEFAULT_SERVER_ADDR,
+            max_clients: 16,
+        }
+    }
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings {
+            bind: DEFAULT_CLIENT_ADDR,
+            server_addr: DEFAULT_SERVER_ADDR,
+            client_id: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) =>
+                write!(f, "could not read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) =>
+                write!(f, "could not parse {}: {}", path.display(), e),
+            ConfigError::Invalid(why) => write!(f, "invalid config: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl NetSettings {
+    pub fn load(path: &Path) -> Result<NetSettings, ConfigError> {
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(NetSettings::default());
+            }
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+
+        ron::from_str(&src)
+            .map_err(|e| ConfigError::Parse(path.to_owned(), e))
+    }
+
+    pub fn from_args(
+        args: &NetArgs,
+        side: NetSide,
+    ) -> Result<NetSettings, ConfigError> {
+        let mut settings = NetSettings::load(&args.config)?;
+
+        let bind = match side {
+            NetSide::Client => &mut settings.client.bind,
+            NetSide::Server => &mut settings.server.bind,
+        };
+        if let Some(ip) = args.bind {
+            bind.set_ip(ip);
+        }
+        if let Some(port) = args.port {
+            bind.set_port(port);
+        }
+
+        if let Some(addr) = args.server_addr {
+            settings.client.server_addr = addr;
+        }
+        if let Some(id) = args.client_id {
+            settings.client.client_id = Some(id);
+        }
+        if let Some(max) = args.max_clients {
+            settings.server.max_clients = max;
+        }
+        if let Some(rate) = args.tick_rate {
+            settings.tick_rate = rate;
+        }
+        if let Some(ms) = args.client_send_interval {
+            settings.client_send_interval_ms = ms;
+        }
+        if let Some(ms) = args.server_send_interval {
+            settings.server_send_interval_ms = ms;
+        }
+
+        if !(settings.tick_rate.is_finite() && settings.tick_rate > 0.0) {
+            return Err(ConfigError::Invalid("tick rate must be positive"));
+        }
+
+        Ok(settings)
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+
+    pub fn client_send_interval(&self) -> Duration {
+        Duration::from_millis(self.client_send_interval_ms)
+    }
+
+    pub fn server_send_interval(&self) -> Duration {
+        Duration::from_millis(self.server_send_interval_ms)
+    }
+
+    pub fn client_id(&self) -> u64 {
+        self.client.client_id.unwrap_or_else(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            nanos ^ ((std::process::id() as u64) << 32)
+        })
+    }
+}
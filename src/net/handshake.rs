@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use bevy::utils::HashSet;
+
+use crate::*;
+use crate::version::{Version, VERSION, VERSION_STR};
+use crate::magic::spells::{self, Spells};
+use crate::voxel::{self, Voxels};
+
+// how long a client gets to introduce itself before it's dropped
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// gives a rejection message a chance to arrive before the connection closes
+pub const DISCONNECT_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Registries(pub BTreeMap<String, u64>);
+
+impl Default for Registries {
+    fn default() -> Self {
+        let mut voxels = Voxels::default();
+        voxel::register_default_voxels(&mut voxels);
+
+        let mut spells = Spells::default();
+        spells::register_default_spells(&mut spells);
+
+        let mut map = BTreeMap::new();
+        map.insert("voxels".to_owned(), voxels.registry_hash());
+        map.insert("spells".to_owned(), spells.registry_hash());
+        Registries(map)
+    }
+}
+
+// registries whose hash isn't final yet, like spells from a book that's
+// still loading. handshakes wait for these to finish
+#[derive(Resource, Default, Debug)]
+pub struct LoadingRegistries(pub HashSet<String>);
+
+impl LoadingRegistries {
+    pub fn done(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Registries {
+    pub fn insert(&mut self, name: &str, hash: u64) {
+        self.0.insert(name.to_owned(), hash);
+    }
+
+    // the first registry that doesn't line up with `other`, if any
+    pub fn mismatch(&self, other: &Registries) -> Option<String> {
+        self.0.keys()
+            .chain(other.0.keys())
+            .find(|k| self.0.get(*k) != other.0.get(*k))
+            .cloned()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Hello {
+    pub version: Version,
+    pub version_str: String,
+    pub registries: Registries,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Welcome {
+    pub version_str: String,
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum DisconnectReason {
+    IncompatibleVersion {
+        server: String,
+        client: String,
+    },
+    RegistryMismatch {
+        registry: String,
+    },
+    ServerFull,
+    HandshakeTimeout,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisconnectReason::IncompatibleVersion { server, client } =>
+                write!(f, "incompatible versions: the server is running {} \
+                    but you are running {}", server, client),
+            DisconnectReason::RegistryMismatch { registry } =>
+                write!(f, "your {} do not match the server's", registry),
+            DisconnectReason::ServerFull => write!(f, "the server is full"),
+            DisconnectReason::HandshakeTimeout =>
+                write!(f, "timed out waiting for the handshake"),
+        }
+    }
+}
+
+pub fn check_hello(
+    hello: &Hello,
+    registries: &Registries,
+) -> Result<(), DisconnectReason> {
+    if !VERSION.is_compatible(&hello.version) {
+        return Err(DisconnectReason::IncompatibleVersion {
+            server: VERSION_STR.to_owned(),
+            client: hello.version_str.clone(),
+        });
+    }
+
+    if let Some(registry) = registries.mismatch(&hello.registries) {
+        return Err(DisconnectReason::RegistryMismatch { registry });
+    }
+
+    Ok(())
+}
+
+#[derive(Channel)]
+pub struct HandshakeChannel;
+
+pub(super) fn register(app: &mut App) {
+    app.init_resource::<Registries>();
+    app.init_resource::<LoadingRegistries>();
+    app.add_message::<Hello>(ChannelDirection::ClientToServer);
+    app.add_message::<Welcome>(ChannelDirection::ServerToClient);
+    app.add_message::<DisconnectReason>(ChannelDirection::ServerToClient);
+    app.add_channel::<HandshakeChannel>(ChannelSettings {
+        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+        direction: ChannelDirection::Bidirectional,
+        ..default()
+    });
+}
@@ -0,0 +1,42 @@
+use crate::*;
+use client::{ClientCommands, ClientPlugins};
+use server::{ServerCommands, ServerPlugins};
+use super::config::NetSettings;
+use super::protocol::shared_config;
+
+// in host mode the server and the local client live in the same app, with
+// the client talking to the server through lightyear's local connection
+// rather than a socket. remote players still connect over udp.
+pub fn host_plugins(settings: &NetSettings) -> (ClientPlugins, ServerPlugins) {
+    let shared = shared_config(settings, Mode::HostServer);
+    let server = super::server::server_plugin(shared.clone(), settings);
+    let client = ClientPlugins::new(client::ClientConfig {
+        shared,
+        net: client::NetConfig::Local {
+            id: settings.client_id(),
+        },
+        ..default()
+    });
+
+    (client, server)
+}
+
+pub struct HostPlugin;
+
+impl Plugin for HostPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(super::ProtocolPlugin)
+            .add_plugins(super::server::ServerNetPlugin)
+            .add_plugins(super::client::ClientNetPlugin)
+            .add_systems(Startup, start_host)
+        ;
+    }
+}
+
+fn start_host(mut commands: Commands, settings: Res<NetSettings>) {
+    // the server has to be up before the local client can join it
+    commands.start_server();
+    commands.connect_client();
+    info!("hosting on {}", settings.server.bind);
+}
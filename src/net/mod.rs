@@ -1,5 +1,9 @@
 pub mod client;
+pub mod config;
+pub mod handshake;
+pub mod host;
 pub mod server;
 pub mod protocol;
 
+pub use config::{NetArgs, NetSettings, NetSide};
 pub use protocol::ProtocolPlugin;
@@ -1,24 +1,30 @@
-use std::time::Duration;
-
 use crate::*;
+use crate::magic::projectile::{Projectile, ProjectileMotion};
+use crate::magic::status::VisibleStatuses;
+use crate::npc::{Npc, NpcMotion};
+use super::config::NetSettings;
 
-pub const PROTOCOL_ID: u64 = 0x1234abcd00000000
-    | version::VERSION.protocol_v();
+// only bump this when the handshake itself changes; version mismatches are
+// reported by the handshake instead of failing to connect
+pub const PROTOCOL_ID: u64 = 0x1234abcd00000001;
 
-pub fn shared_config() -> SharedConfig {
+pub fn shared_config(settings: &NetSettings, mode: Mode) -> SharedConfig {
     SharedConfig {
-        client_send_interval: Duration::default(),
-        server_send_interval: Duration::from_millis(40),
+        client_send_interval: settings.client_send_interval(),
+        server_send_interval: settings.server_send_interval(),
         tick: TickConfig {
-            tick_duration: Duration::from_secs_f32(1.00 / 64.0),
+            tick_duration: settings.tick_duration(),
         },
-        mode: Mode::Separate,
+        mode,
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct MessageUsi(pub usize);
 
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PlayerId(pub ClientId);
+
 #[derive(Channel)]
 pub struct MyChannel;
 
@@ -26,7 +32,16 @@ pub struct ProtocolPlugin;
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
+        super::handshake::register(app);
         app.add_message::<MessageUsi>(ChannelDirection::Bidirectional);
+        app.register_component::<PlayerId>(ChannelDirection::ServerToClient);
+        app.register_component::<Projectile>(ChannelDirection::ServerToClient);
+        app.register_component::<ProjectileMotion>(
+            ChannelDirection::ServerToClient);
+        app.register_component::<VisibleStatuses>(
+            ChannelDirection::ServerToClient);
+        app.register_component::<Npc>(ChannelDirection::ServerToClient);
+        app.register_component::<NpcMotion>(ChannelDirection::ServerToClient);
         app.add_channel::<MyChannel>(ChannelSettings {
             mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
             direction: ChannelDirection::Bidirectional,
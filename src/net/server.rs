@@ -1,16 +1,35 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use bevy::utils::HashMap;
 
 use crate::*;
+use crate::version::VERSION_STR;
 use server::*;
-use super::protocol::PROTOCOL_ID;
-
-pub const ADDR: SocketAddr =
-    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 42069);
+use super::config::NetSettings;
+use super::handshake::{
+    check_hello, DisconnectReason, HandshakeChannel, Hello, LoadingRegistries,
+    Registries, Welcome, DISCONNECT_DELAY, HANDSHAKE_TIMEOUT,
+};
+use super::client::LocalClientId;
+use super::protocol::{PlayerId, PROTOCOL_ID};
+use crate::client_plugin::Player;
+use crate::magic::projectile::Projectile;
+use crate::magic::status::VisibleStatuses;
+use crate::npc::Npc;
 
 pub fn server_plugin(
     shared: SharedConfig,
+    settings: &NetSettings,
+) -> ServerPlugins {
+    let io = IoConfig::from_transport(
+        ServerTransport::UdpSocket(settings.server.bind));
+    server_plugin_with_io(shared, io)
+}
+
+pub fn server_plugin_with_io(
+    shared: SharedConfig,
+    io: IoConfig,
 ) -> ServerPlugins {
-    let io = IoConfig::from_transport(ServerTransport::UdpSocket(ADDR));
     ServerPlugins {
         config: ServerConfig {
             shared,
@@ -27,3 +46,253 @@ pub fn server_plugin(
         }
     }
 }
+
+pub struct ServerNetPlugin;
+
+impl Plugin for ServerNetPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Handshakes>()
+            .add_systems(Update, (
+                on_connect,
+                on_hello,
+                check_registries,
+                expire_handshakes,
+                on_disconnect,
+            ).chain())
+            .add_systems(PostUpdate, (
+                replicate_projectiles,
+                replicate_npcs,
+                share_host_statuses,
+            ))
+        ;
+    }
+}
+
+#[derive(Resource, Default)]
+struct Handshakes {
+    // clients that have connected but not said hello yet
+    pending: HashMap<ClientId, Duration>,
+    // hellos held back until our own registries have finished loading
+    hellos: Vec<(ClientId, Hello)>,
+    // the registries each joined client last told us about
+    accepted: HashMap<ClientId, Registries>,
+    // joined clients whose registries stopped matching, and since when
+    out_of_sync: HashMap<ClientId, Duration>,
+    // rejected clients and when to actually drop them
+    kicks: Vec<(ClientId, Duration)>,
+}
+
+impl Handshakes {
+    fn reject(
+        &mut self,
+        conn: &mut ConnectionManager,
+        now: Duration,
+        client_id: ClientId,
+        reason: DisconnectReason,
+    ) {
+        info!("rejecting {}: {}", client_id, reason);
+        self.forget(client_id);
+        if conn.send_message::<HandshakeChannel, _>(client_id, &reason)
+            .is_err()
+        {
+            error!("could not tell {} why it was rejected", client_id);
+        }
+        self.kicks.push((client_id, now + DISCONNECT_DELAY));
+    }
+
+    fn forget(&mut self, client_id: ClientId) {
+        self.pending.remove(&client_id);
+        self.hellos.retain(|(id, _)| *id != client_id);
+        self.accepted.remove(&client_id);
+        self.out_of_sync.remove(&client_id);
+    }
+}
+
+fn on_connect(
+    mut connections: EventReader<ConnectEvent>,
+    mut conn: ResMut<ConnectionManager>,
+    mut handshakes: ResMut<Handshakes>,
+    settings: Res<NetSettings>,
+    time: Res<Time>,
+) {
+    for c in connections.read() {
+        if conn.connected_clients().count() > settings.server.max_clients {
+            handshakes.reject(&mut conn, time.elapsed(), c.client_id,
+                DisconnectReason::ServerFull);
+            continue;
+        }
+
+        info!("{} has connected to the server", c.client_id);
+        handshakes.pending.insert(c.client_id, time.elapsed());
+    }
+}
+
+fn on_hello(
+    mut commands: Commands,
+    mut hellos: EventReader<MessageEvent<Hello>>,
+    mut conn: ResMut<ConnectionManager>,
+    mut handshakes: ResMut<Handshakes>,
+    registries: Res<Registries>,
+    loading: Res<LoadingRegistries>,
+    time: Res<Time>,
+) {
+    handshakes.hellos.extend(
+        hellos.read().map(|m| (m.context, m.message.clone())));
+    // checking against hashes that are about to change would let the wrong
+    // clients in
+    if !loading.done() {
+        return;
+    }
+
+    for (client_id, hello) in std::mem::take(&mut handshakes.hellos) {
+        // joined clients say hello again when a reload changes their
+        // registries, and check_registries deals with the result
+        if let Some(theirs) = handshakes.accepted.get_mut(&client_id) {
+            *theirs = hello.registries;
+            continue;
+        }
+
+        if handshakes.pending.remove(&client_id).is_none() {
+            continue;
+        }
+
+        if let Err(reason) = check_hello(&hello, &registries) {
+            handshakes.reject(&mut conn, time.elapsed(), client_id, reason);
+            continue;
+        }
+
+        info!("{} is running {}", client_id, hello.version_str);
+        handshakes.accepted.insert(client_id, hello.registries);
+        let welcome = Welcome { version_str: VERSION_STR.to_owned() };
+        if conn.send_message::<HandshakeChannel, _>(client_id, &welcome)
+            .is_err()
+        {
+            error!("could not welcome {}", client_id);
+        }
+        commands.spawn((PlayerId(client_id), Replicate::default()));
+    }
+}
+
+// a hot reload on either side can leave a joined client out of sync. it
+// gets as long as a handshake would to catch up before it's dropped, since
+// the other side is often about to reload the same change
+fn check_registries(
+    mut conn: ResMut<ConnectionManager>,
+    mut handshakes: ResMut<Handshakes>,
+    registries: Res<Registries>,
+    loading: Res<LoadingRegistries>,
+    time: Res<Time>,
+) {
+    if !loading.done() {
+        return;
+    }
+
+    let now = time.elapsed();
+    let mut expired = Vec::new();
+    let Handshakes { accepted, out_of_sync, .. } = &mut *handshakes;
+    for (&client_id, theirs) in accepted.iter() {
+        let Some(registry) = registries.mismatch(theirs)
+        else {
+            out_of_sync.remove(&client_id);
+            continue;
+        };
+
+        let since = *out_of_sync.entry(client_id).or_insert(now);
+        if now - since > HANDSHAKE_TIMEOUT {
+            expired.push((client_id, registry));
+        }
+    }
+
+    for (client_id, registry) in expired {
+        handshakes.reject(&mut conn, now, client_id,
+            DisconnectReason::RegistryMismatch { registry });
+    }
+}
+
+fn expire_handshakes(
+    mut servers: ResMut<ServerConnections>,
+    mut conn: ResMut<ConnectionManager>,
+    mut handshakes: ResMut<Handshakes>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+    let expired: Vec<_> = handshakes.pending.iter()
+        .filter(|(_, &since)| now - since > HANDSHAKE_TIMEOUT)
+        .map(|(&id, _)| id)
+        .collect();
+    for client_id in expired {
+        handshakes.reject(&mut conn, now, client_id,
+            DisconnectReason::HandshakeTimeout);
+    }
+
+    handshakes.kicks.retain(|&(client_id, at)| {
+        if at > now {
+            return true;
+        }
+
+        if servers.disconnect(client_id).is_err() {
+            error!("could not disconnect {}", client_id);
+        }
+        false
+    });
+}
+
+fn on_disconnect(
+    mut commands: Commands,
+    mut disconnects: EventReader<DisconnectEvent>,
+    mut handshakes: ResMut<Handshakes>,
+    players: Query<(Entity, &PlayerId)>,
+) {
+    for d in disconnects.read() {
+        info!("{} has disconnected from the server", d.client_id);
+        handshakes.forget(d.client_id);
+        handshakes.kicks.retain(|&(id, _)| id != d.client_id);
+        for (entity, player) in players.iter() {
+            if player.0 == d.client_id {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+// casts are server authoritative, so every projectile the server launches
+// gets sent out to the clients
+fn replicate_projectiles(
+    mut commands: Commands,
+    projectiles: Query<Entity, Added<Projectile>>,
+) {
+    for entity in projectiles.iter() {
+        commands.entity(entity).insert(Replicate::default());
+    }
+}
+
+fn replicate_npcs(
+    mut commands: Commands,
+    npcs: Query<Entity, Added<Npc>>,
+) {
+    for entity in npcs.iter() {
+        commands.entity(entity).insert(Replicate::default());
+    }
+}
+
+// players aren't replicated, only their ids are, so the host's statuses
+// are copied onto its id for everyone else to see. the other players only
+// exist on their own clients, which work their statuses out themselves
+fn share_host_statuses(
+    mut commands: Commands,
+    local: Option<Res<LocalClientId>>,
+    players: Query<Ref<VisibleStatuses>, With<Player>>,
+    ids: Query<(Entity, Ref<PlayerId>)>,
+) {
+    let (Some(local), Ok(statuses)) = (local, players.get_single())
+    else {
+        return;
+    };
+
+    for (entity, id) in ids.iter() {
+        if id.0 == local.0 && (statuses.is_changed() || id.is_added()) {
+            commands.entity(entity).insert((*statuses).clone());
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use crate::*;
+use magic::MagicElement;
+use magic::casting::{CastSpell, Casting, Cooldowns, SpellSlots, SpellTarget};
+use magic::components::{Health, MagicCaster};
+use magic::damage::{effectiveness, Dead};
+use magic::explosion::DAMAGE_PER_POWER;
+use magic::spells::{Spell, SpellEffect, SpellId, Spells, Targeting};
+use magic::status::StatusEffects;
+use npc::{npc_agent, Npc, NpcMotion, NpcState, NPC_HEIGHT};
+use voxel::VoxelRes;
+use voxel::path::{PathCache, PathFollower};
+
+// seconds between decisions
+pub const THINK_INTERVAL: f32 = 0.25;
+
+// how much a status is worth next to a point of damage
+pub const STATUS_VALUE: f32 = 5.0;
+
+// npcs below this much health value healing over hurting
+pub const HURT_BELOW: f32 = 0.5;
+
+pub const FALL_SPEED: f32 = 9.81;
+
+// where npcs cast from and look out of, above their feet
+pub const EYE_HEIGHT: f32 = 0.8;
+
+// a chasing npc keeps its path until its target wanders this many cells
+// from the end of it
+pub const REPATH_DISTANCE: i32 = 3;
+
+// server side only, like ProjectileOwner
+#[derive(Component, Clone, Debug)]
+pub struct NpcBrain {
+    pub target: Option<Entity>,
+    pub sight_range: f32,
+    // it tries to keep its target between these distances
+    pub min_range: f32,
+    pub max_range: f32,
+    // runs off when its mana drops below the first fraction of its max,
+    // and only comes back once it's above the second
+    pub retreat_below: f32,
+    pub resume_above: f32,
+    pub speed: f32,
+    // the way to its target while chasing it
+    pub path: PathFollower,
+    think: f32,
+}
+
+impl Default for NpcBrain {
+    fn default() -> Self {
+        NpcBrain {
+            target: None,
+            sight_range: 30.0,
+            min_range: 6.0,
+            max_range: 15.0,
+            retreat_below: 0.2,
+            resume_above: 0.7,
+            speed: 5.0,
+            path: PathFollower::default(),
+            think: 0.0,
+        }
+    }
+}
+
+// what an npc knows when it decides what to do
+#[derive(Copy, Clone, Debug)]
+pub struct Senses {
+    // to its target, if it can see one
+    pub distance: Option<f32>,
+    pub target_element: MagicElement,
+    pub health: f32,
+    pub mana: f32,
+}
+
+pub fn next_state(state: NpcState, brain: &NpcBrain, s: &Senses) -> NpcState {
+    let Some(distance) = s.distance
+    else {
+        return NpcState::Idle;
+    };
+
+    let low = s.mana < brain.retreat_below;
+    let recovered = s.mana >= brain.resume_above;
+    if low || (state == NpcState::Retreat && !recovered) {
+        return NpcState::Retreat;
+    }
+
+    if distance > brain.max_range {
+        NpcState::Chase
+    } else {
+        NpcState::Attack
+    }
+}
+
+// how worthwhile a spell is right now, or 0 if it's no use
+pub fn spell_score(spell: &Spell, element: MagicElement, s: &Senses) -> f32 {
+    let Some(distance) = s.distance
+    else {
+        return 0.0;
+    };
+
+    let mut damage = 0.0;
+    let mut heal = 0.0;
+    for effect in spell.effects.iter() {
+        match *effect {
+            SpellEffect::Damage { amount } => damage += amount as f32,
+            SpellEffect::Projectile { damage: d, .. } => damage += d as f32,
+            SpellEffect::Explosion { power, .. } =>
+                damage += power * DAMAGE_PER_POWER,
+            SpellEffect::Heal { amount } => heal += amount as f32,
+            SpellEffect::Status { .. } if effect.status(element).is_some() =>
+                damage += STATUS_VALUE,
+            _ => (),
+        }
+    }
+
+    let (reach, damage) = match spell.targeting {
+        // anything it'd do to a target it'd do to itself
+        Targeting::Caster => (f32::INFINITY, 0.0),
+        Targeting::Entity { range } | Targeting::Point { range } =>
+            (range, damage),
+        Targeting::Direction => (f32::INFINITY, damage),
+    };
+    if distance > reach {
+        return 0.0;
+    }
+
+    let heal = if s.health < HURT_BELOW { heal * 2.0 } else { 0.0 };
+    damage * effectiveness(element, s.target_element) + heal
+}
+
+// the best spell it can cast right now. ties go to the earlier slot
+pub fn pick_spell(
+    spells: &Spells,
+    slots: &[SpellId],
+    caster: &MagicCaster,
+    cooldowns: Option<&Cooldowns>,
+    s: &Senses,
+) -> Option<SpellId> {
+    let mut best = None;
+    let mut best_score = 0.0;
+    for &id in slots {
+        let Some(spell) = spells.get(id)
+        else {
+            continue;
+        };
+        let cooling = cooldowns.is_some_and(|c| c.remaining(id) > 0.0);
+        if cooling || !caster.can_afford(&spell.cost) {
+            continue;
+        }
+
+        let score = spell_score(spell, spell.resolve_element(caster), s);
+        if score > best_score {
+            best = Some(id);
+            best_score = score;
+        }
+    }
+    best
+}
+
+pub(crate) fn think(
+    time: Res<Time>,
+    spells: Res<Spells>,
+    voxels: Res<VoxelRes>,
+    mut paths: ResMut<PathCache>,
+    mut npcs: Query<(Entity, &mut NpcBrain, &mut Npc, &GlobalTransform,
+        &MagicCaster, &Health, &SpellSlots, Option<&Cooldowns>,
+        Has<Casting>, Option<&StatusEffects>), Without<Dead>>,
+    targets: Query<(Entity, &GlobalTransform, &Health),
+        (Without<Npc>, Without<Dead>)>,
+    mut casts: EventWriter<CastSpell>,
+) {
+    let Ok(voxels) = voxels.try_read()
+    else {
+        return;
+    };
+
+    for (entity, mut brain, mut npc, trans, caster, health, slots,
+        cooldowns, casting, statuses) in npcs.iter_mut()
+    {
+        brain.think -= time.delta_seconds();
+        if brain.think > 0.0 {
+            continue;
+        }
+        brain.think += THINK_INTERVAL;
+
+        // the closest thing it can see
+        let eye = trans.translation() + Vec3::Y * EYE_HEIGHT;
+        let seen = targets.iter()
+            .map(|(e, t, h)| (e, t.translation(), h))
+            .filter(|(_, pos, _)| {
+                pos.distance(eye) <= brain.sight_range
+                    && voxels.line_of_sight(eye, *pos)
+            })
+            .min_by(|a, b| a.1.distance(eye).total_cmp(&b.1.distance(eye)));
+        brain.target = seen.map(|(e, _, _)| e);
+
+        let mana = (caster.mana_a + caster.mana_b) as f32
+            / (caster.max_mana_a + caster.max_mana_b).max(1) as f32;
+        let senses = Senses {
+            distance: seen.map(|(_, pos, _)| pos.distance(eye)),
+            target_element: seen.map(|(_, _, h)| h.typed)
+                .unwrap_or(MagicElement::NonElemental),
+            health: health.health as f32 / health.max_health.max(1) as f32,
+            mana,
+        };
+
+        let state = next_state(npc.state, &brain, &senses);
+        if npc.state != state {
+            npc.state = state;
+        }
+
+        let chasing = match (state, seen) {
+            (NpcState::Chase, Some((_, pos, _))) => Some(pos),
+            _ => None,
+        };
+        let agent = npc_agent();
+        let goal = chasing.and_then(|pos| agent.standing_cell(&*voxels, pos));
+        // the cache only helps when neither end has moved, so instead of
+        // searching every think it keeps its path while the target stays
+        // near the end of it and nothing along it has changed
+        let close = |end: IVec3, goal: IVec3|
+            (end - goal).abs().max_element() <= REPATH_DISTANCE;
+        let keep = goal.zip(brain.path.goal())
+            .is_some_and(|(goal, end)| close(end, goal))
+            && brain.path.unchanged(&voxels);
+        if !keep {
+            brain.path = PathFollower::default();
+        }
+        if let (false, Some(goal)) = (keep, goal) {
+            let feet = trans.translation() - Vec3::Y * NPC_HEIGHT / 2.0;
+            let start = agent.standing_cell(&*voxels, feet);
+            let path = start.and_then(|start|
+                paths.find(&voxels, agent, start, goal));
+            if let Some(path) = path {
+                brain.path = PathFollower::tracked(&voxels, agent, path);
+            }
+        }
+
+        let can_cast = statuses.map(|s| s.can_cast()).unwrap_or(true);
+        if state != NpcState::Attack || casting || !can_cast {
+            continue;
+        }
+        let (Some((target, pos, _)), Some(spell)) = (seen,
+            pick_spell(&spells, &slots.0, caster, cooldowns, &senses))
+        else {
+            continue;
+        };
+
+        let target = match spell.spell(&spells).targeting {
+            Targeting::Caster => SpellTarget::Caster,
+            Targeting::Entity { .. } => SpellTarget::Entity(target),
+            Targeting::Point { .. } => SpellTarget::Point(pos),
+            Targeting::Direction => SpellTarget::Direction(pos - eye),
+        };
+        casts.send(CastSpell { caster: entity, spell, target });
+    }
+}
+
+// walks towards, away from or around its target depending on its state
+pub(crate) fn steer(
+    time: Res<Time>,
+    mut npcs: Query<(&mut NpcBrain, &Npc,
+        &mut KinematicCharacterController, &mut Transform,
+        Option<&StatusEffects>), Without<Dead>>,
+    targets: Query<&GlobalTransform>,
+) {
+    for (mut brain, npc, mut cont, mut trans, statuses) in npcs.iter_mut() {
+        let fall = Vec3::NEG_Y * FALL_SPEED * time.delta_seconds();
+        let target = brain.target.and_then(|t| targets.get(t).ok());
+        let Some(target) = target.map(|t| t.translation())
+        else {
+            cont.translation = Some(fall);
+            continue;
+        };
+
+        let mut to = target - trans.translation;
+        to.y = 0.0;
+        let distance = to.length();
+        let to = to.normalize_or_zero();
+        if to != Vec3::ZERO {
+            trans.look_to(to, Vec3::Y);
+        }
+
+        let dir = match npc.state {
+            NpcState::Idle => Vec3::ZERO,
+            // around whatever's in the way, if it found a path
+            NpcState::Chase => {
+                let feet = trans.translation - Vec3::Y * NPC_HEIGHT / 2.0;
+                brain.path.steer(feet)
+                    .map(|d| Vec3::new(d.x, 0.0, d.z).normalize_or_zero())
+                    .unwrap_or(to)
+            }
+            NpcState::Retreat => -to,
+            NpcState::Attack if distance < brain.min_range => -to,
+            // circle around it while casting
+            NpcState::Attack => to.cross(Vec3::Y),
+        };
+
+        let speed = statuses.map(|s| s.speed_multiplier()).unwrap_or(1.0);
+        let step = dir * brain.speed * speed * time.delta_seconds();
+        cont.translation = Some(step + fall);
+    }
+}
+
+pub(crate) fn update_npc_motion(
+    mut npcs: Query<(&Transform, &mut NpcMotion), With<NpcBrain>>,
+) {
+    for (trans, mut motion) in npcs.iter_mut() {
+        let position = trans.translation.into();
+        let (yaw, _, _) = trans.rotation.to_euler(EulerRot::YXZ);
+        if motion.position != position || motion.yaw != yaw {
+            *motion = NpcMotion { position, yaw };
+        }
+    }
+}
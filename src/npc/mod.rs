@@ -0,0 +1,144 @@
+use crate::*;
+use magic::{ManaColor, MagicElement};
+use magic::casting::SpellSlots;
+use magic::components::{Health, MagicCaster};
+use magic::mana::ManaRegen;
+use magic::spells::Spells;
+use voxel::path::{Agent, MAX_FALL, STEP_HEIGHT};
+
+pub mod brain;
+
+pub const NPC_HEIGHT: f32 = 2.0;
+pub const NPC_RADIUS: f32 = 0.4;
+
+// what the pathfinder needs to know about npcs
+pub fn npc_agent() -> Agent {
+    Agent::new(NPC_HEIGHT, STEP_HEIGHT, MAX_FALL)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NpcState {
+    // nobody in sight
+    Idle,
+    // closing in on its target
+    Chase,
+    // in range and casting
+    Attack,
+    // backing off to get its mana back
+    Retreat,
+}
+
+// replicated so clients know what to draw
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Npc {
+    pub element: MagicElement,
+    pub state: NpcState,
+}
+
+// where an npc is, kept up to date by the server like ProjectileMotion
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NpcMotion {
+    pub position: [f32; 3],
+    pub yaw: f32,
+}
+
+pub fn npc_bundle(
+    spells: &Spells,
+    pos: Vec3,
+    colors: (ManaColor, ManaColor),
+    tick: f32,
+) -> impl Bundle {
+    let caster = MagicCaster::new(colors.0, colors.1, 60, 60);
+    let element = caster.primary;
+    let slots = ["spark", "bolt", "mend"].iter()
+        .filter_map(|name| spells.id_from_name(name))
+        .collect();
+
+    (
+        TransformBundle::from_transform(Transform::from_translation(pos)),
+        Npc { element, state: NpcState::Idle },
+        NpcMotion { position: pos.into(), yaw: 0.0 },
+        brain::NpcBrain::default(),
+        RigidBody::KinematicPositionBased,
+        Collider::capsule_y(NPC_HEIGHT / 2.0 - NPC_RADIUS, NPC_RADIUS),
+        KinematicCharacterController {
+            offset: CharacterLength::Absolute(0.01),
+            autostep: Some(CharacterAutostep {
+                max_height: CharacterLength::Absolute(STEP_HEIGHT),
+                min_width: CharacterLength::Absolute(0.3),
+                include_dynamic_bodies: false,
+            }),
+            snap_to_ground: Some(CharacterLength::Absolute(STEP_HEIGHT)),
+            ..default()
+        },
+        caster,
+        Health {
+            health: 50,
+            max_health: 50,
+            typed: element,
+        },
+        ManaRegen::per_second(1.5, 1.5, tick),
+        SpellSlots(slots),
+    )
+}
+
+fn spawn_npcs(
+    mut commands: Commands,
+    spells: Res<Spells>,
+    fixed: Res<Time<Fixed>>,
+) {
+    use ManaColor::*;
+    let tick = fixed.timestep().as_secs_f32();
+    for (pos, colors) in [
+        (Vec3::new(-6.0, 8.0, -10.0), (Blue, Yellow)),
+        (Vec3::new(14.0, 8.0, -12.0), (Red, Blue)),
+    ] {
+        commands.spawn(npc_bundle(&spells, pos, colors, tick));
+    }
+}
+
+// replicated npcs have no brain or physics of their own, they just follow
+// what the server says
+fn follow_npc_motion(
+    mut commands: Commands,
+    mut npcs: Query<(Entity, &NpcMotion, Option<&mut Transform>),
+        (With<Npc>, Without<brain::NpcBrain>)>,
+) {
+    for (entity, motion, trans) in npcs.iter_mut() {
+        let transform = Transform::from_translation(motion.position.into())
+            .with_rotation(Quat::from_rotation_y(motion.yaw));
+        match trans {
+            Some(mut trans) => *trans = transform,
+            None => {
+                commands.entity(entity)
+                    .insert(TransformBundle::from_transform(transform));
+            }
+        }
+    }
+}
+
+// the npcs themselves, which only run wherever the game is simulated:
+// singleplayer or the host. clients get them through replication
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, spawn_npcs)
+            .add_systems(Update, (
+                brain::think,
+                brain::steer,
+                brain::update_npc_motion,
+            ).chain())
+        ;
+    }
+}
+
+// moves replicated npcs to where the server says they are
+pub struct NpcClientPlugin;
+
+impl Plugin for NpcClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, follow_npc_motion);
+    }
+}
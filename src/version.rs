@@ -1,13 +1,30 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
 pub const VERSION: Version = Version {
-    major: 0,
-    minor: 0,
-    patch: 0,
-    unpublished_v: 1,
+    major: parse_u8(env!("CARGO_PKG_VERSION_MAJOR")),
+    minor: parse_u8(env!("CARGO_PKG_VERSION_MINOR")),
+    patch: parse_u8(env!("CARGO_PKG_VERSION_PATCH")),
+    unpublished_v: parse_u8(env!("CARGO_PKG_VERSION_PRE")),
 };
 
-pub const VERSION_STR: &str = "0.0.0-0";
+pub const VERSION_STR: &str = env!("CARGO_PKG_VERSION");
+
+pub const GIT_HASH: &str = env!("MAGIC_GIT_HASH");
+
+pub const BUILD_DATE: &str = env!("MAGIC_BUILD_DATE");
+
+pub const LONG_VERSION_STR: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (", env!("MAGIC_GIT_HASH"), " ", env!("MAGIC_BUILD_DATE"), ")",
+);
 
-#[derive(Copy, Clone, Debug)]
+// a version of 1.2.3-4 is the fourth unpublished build on the way to 1.2.3.
+// published builds have an unpublished_v of 0
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
@@ -22,4 +39,123 @@ impl Version {
             | ((self.patch as u64) << 8)
             | ((self.unpublished_v as u64) << 0)
     }
+
+    // builds can play together as long as only the patch level differs.
+    // unpublished builds have no guarantees, so they have to match exactly
+    pub const fn is_compatible(&self, other: &Version) -> bool {
+        self.major == other.major
+            && self.minor == other.minor
+            && self.unpublished_v == other.unpublished_v
+    }
+
+    pub const fn is_published(&self) -> bool {
+        self.unpublished_v == 0
+    }
+
+    pub fn parse(s: &str) -> Result<Version, ParseVersionError> {
+        let (release, unpublished) = match s.split_once('-') {
+            Some((release, pre)) => (release, Some(pre)),
+            None => (s, None),
+        };
+
+        let mut parts = release.split('.');
+        let mut next = |part: &'static str| {
+            parts.next()
+                .ok_or(ParseVersionError::Missing(part))
+                .and_then(|p| p.parse::<u8>()
+                    .map_err(|_| ParseVersionError::BadNumber(p.to_owned())))
+        };
+        let major = next("major")?;
+        let minor = next("minor")?;
+        let patch = next("patch")?;
+        if parts.next().is_some() {
+            return Err(ParseVersionError::TrailingParts);
+        }
+
+        let unpublished_v = match unpublished {
+            Some(p) => p.parse::<u8>()
+                .map_err(|_| ParseVersionError::BadNumber(p.to_owned()))?,
+            None => 0,
+        };
+
+        Ok(Version { major, minor, patch, unpublished_v })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.is_published(), other.is_published()) {
+                // the release comes after all of its unpublished builds
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.unpublished_v.cmp(&other.unpublished_v),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.is_published() {
+            write!(f, "-{}", self.unpublished_v)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Version::parse(s)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseVersionError {
+    Missing(&'static str),
+    BadNumber(String),
+    TrailingParts,
+}
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseVersionError::Missing(part) =>
+                write!(f, "missing {} version", part),
+            ParseVersionError::BadNumber(n) =>
+                write!(f, "{:?} is not a valid version number", n),
+            ParseVersionError::TrailingParts =>
+                write!(f, "expected major.minor.patch[-unpublished]"),
+        }
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+// cargo hands us the version pieces as strings, so they have to be parsed at
+// compile time. an empty string (no pre-release) is 0. anything else fails
+// the build, see the note on `version` in Cargo.toml
+const fn parse_u8(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    let mut n: u32 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let d = bytes[i];
+        assert!(d.is_ascii_digit(),
+            "version parts and the pre-release must be plain numbers");
+        n = n * 10 + (d - b'0') as u32;
+        assert!(n <= u8::MAX as u32, "version parts can't be over 255");
+        i += 1;
+    }
+    n as u8
 }
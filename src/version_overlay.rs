@@ -0,0 +1,31 @@
+use magic_game::*;
+use magic_game::version::LONG_VERSION_STR;
+
+#[derive(Component)]
+pub struct VersionOverlay;
+
+pub fn setup_version_overlay(
+    mut commands: Commands,
+) {
+    commands.spawn((
+        VersionOverlay,
+        TextBundle {
+            text: Text::from_section(
+                format!("magic game {}", LONG_VERSION_STR),
+                TextStyle {
+                    font_size: 12.0,
+                    color: Color::WHITE.with_a(0.6),
+                    ..default()
+                },
+            ),
+            z_index: ZIndex::Global(i32::MAX),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(1.),
+                bottom: Val::Percent(1.),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
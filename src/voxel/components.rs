@@ -6,13 +6,14 @@ use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::PrimitiveTopology;
 use bevy::tasks::futures_lite::future;
 use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::HashSet;
 use voxel::{CHUNK_SIZE_I32, Voxels, VOXEL_SIZE};
 use voxel::mesh_data::*;
 
 use super::VoxelRes;
 
 #[derive(Component)]
-pub(super) struct Chunk {
+pub struct Chunk {
     pub(super) loaded: bool,
     pub(super) mark: bool,
 }
@@ -50,13 +51,28 @@ impl ConstructChunkMesh {
     }
 }
 
+// a chunk's collider, then its opaque and translucent meshes
+type ChunkMeshes = (Option<Collider>, Mesh, Mesh);
+
 #[derive(Component)]
-pub(super) struct ChunkMeshWaiter(Task<(Collider, Mesh)>);
+pub(super) struct ChunkMeshWaiter {
+    task: Task<ChunkMeshes>,
+    // meshes asked for in the same frame are swapped in together, so an
+    // edit across chunk borders doesn't leave a gap for a frame
+    batch: u64,
+    done: Option<ChunkMeshes>,
+}
+
+// translucent voxels like water need blending, which needs its own
+// material, so they're drawn by a child of the chunk
+#[derive(Component)]
+pub(super) struct TranslucentChunk;
 
 pub(super) fn init_chunk_construction(
     mut commands: Commands,
     voxels: Res<VoxelRes>,
     mut rx: EventReader<ConstructChunkMesh>,
+    mut batch: Local<u64>,
 ) {
     let v = voxels.clone();
     let Ok(voxels) = voxels.try_read()
@@ -64,6 +80,7 @@ pub(super) fn init_chunk_construction(
         return;
     };
 
+    *batch += 1;
     let pool = AsyncComputeTaskPool::get();
     for &ConstructChunkMesh { x, y, z } in rx.read() {
         let Some(chunk) = voxels.get_chunk(x, y, z)
@@ -72,7 +89,11 @@ pub(super) fn init_chunk_construction(
         };
 
         let task = pool.spawn(construct_chunk(x, y, z, v.clone()));
-        commands.entity(chunk.entity).insert(ChunkMeshWaiter(task));
+        commands.entity(chunk.entity).insert(ChunkMeshWaiter {
+            task,
+            batch: *batch,
+            done: None,
+        });
     }
 }
 
@@ -80,37 +101,116 @@ pub(super) fn handle_chunk_mesh_update(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut waiting_chunks: Query<(Entity, &mut ChunkMeshWaiter)>,
+    mut waiting_chunks: Query<(Entity, &mut ChunkMeshWaiter,
+        Option<&Children>)>,
+    translucent: Query<(), With<TranslucentChunk>>,
 ) {
-    for (entity, mut waiter) in waiting_chunks.iter_mut() {
-        let Some((col, mesh)) =
-            future::block_on(future::poll_once(&mut waiter.0))
+    let mut pending = HashSet::new();
+    for (_, mut waiter, _) in waiting_chunks.iter_mut() {
+        if waiter.done.is_none() {
+            waiter.done = future::block_on(future::poll_once(&mut waiter.task));
+        }
+        if waiter.done.is_none() {
+            pending.insert(waiter.batch);
+        }
+    }
+
+    for (entity, mut waiter, children) in waiting_chunks.iter_mut() {
+        if pending.contains(&waiter.batch) {
+            continue;
+        }
+        let Some((col, mesh, see_through)) = waiter.done.take()
         else {
             continue;
         };
 
+        // voxel colours come from the mesh's vertex colours
         let material = StandardMaterial {
-            base_color: Color::rgb_u8(255, 255, 0),
+            base_color: Color::WHITE,
             ..Default::default()
         };
 
-        commands.entity(entity)
-            .insert(col)
+        let child = children.map(|c| c.iter()).into_iter()
+            .flatten()
+            .copied()
+            .find(|&e| translucent.contains(e));
+        match child {
+            Some(child) => {
+                commands.entity(child).insert(meshes.add(see_through));
+            }
+            // most chunks have nothing see-through in them
+            None if see_through.count_vertices() == 0 => {}
+            None => {
+                let blended = StandardMaterial {
+                    base_color: Color::WHITE,
+                    alpha_mode: AlphaMode::Blend,
+                    ..Default::default()
+                };
+                let child = commands.spawn((PbrBundle {
+                    mesh: meshes.add(see_through),
+                    material: materials.add(blended),
+                    ..default()
+                }, TranslucentChunk)).id();
+                commands.entity(entity).add_child(child);
+            }
+        }
+
+        let mut chunk = commands.entity(entity);
+        match col {
+            Some(col) => chunk.insert(col),
+            None => chunk.remove::<Collider>(),
+        };
+        chunk
             .insert(meshes.add(mesh))
             .insert(materials.add(material))
             .remove::<ChunkMeshWaiter>();
     }
 }
 
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn add_face(
+        &mut self,
+        face: usize,
+        vertices: impl Iterator<Item = [f32; 3]>,
+        color: Color,
+    ) {
+        let base = self.vertices.len() as u32;
+        self.indices.extend(CUBE_INDICES.iter().map(|i| i + base));
+        self.normals.extend(&CUBE_NORMALS[face]);
+        self.colors.extend([color.as_linear_rgba_f32(); 4]);
+        self.vertices.extend(vertices);
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+            .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
 async fn construct_chunk(
     chunk_x: i32,
     chunk_y: i32,
     chunk_z: i32,
     voxels: Arc<RwLock<Voxels>>,
-) -> (Collider, Mesh) {
-    let mut vertices: Vec<[f32; 3]> = Vec::new();
-    let mut normals: Vec<[f32; 3]> = Vec::new();
-    let mut indices: Vec<u32> = Vec::new();
+) -> ChunkMeshes {
+    let mut opaque = MeshBuilder::default();
+    let mut see_through = MeshBuilder::default();
+
+    // only solid voxels collide, so the collider gets its own faces
+    let mut col_vertices: Vec<Vec3> = Vec::new();
+    let mut col_indices: Vec<[u32; 3]> = Vec::new();
 
     let chx = CHUNK_SIZE_I32 * chunk_x;
     let chy = CHUNK_SIZE_I32 * chunk_y;
@@ -118,10 +218,11 @@ async fn construct_chunk(
     for x in 0..CHUNK_SIZE_I32 {
         for y in 0..CHUNK_SIZE_I32 {
             for z in 0..CHUNK_SIZE_I32 {
-                let voxel = voxels.read().unwrap()
-                    .get_block(chx + x, chy + y, chz + z);
+                let voxels = voxels.read().unwrap();
+                let voxel = voxels.get_block(chx + x, chy + y, chz + z);
+                let config = voxel.config(&voxels);
 
-                if !voxel.config(&*voxels.read().unwrap()).render {
+                if !config.render && !config.solid {
                     continue;
                 }
 
@@ -134,38 +235,50 @@ async fn construct_chunk(
                         (0, -1, 0),
                         (0, 0, -1),
                     ][face];
-                    let neighbor = voxels.read().unwrap()
-                        .get_block(
-                            chx + x + x_off, chy + y + y_off, chz + z + z_off);
-
-                    if neighbor.config(&*voxels.read().unwrap()).render {
-                        continue;
-                    }
+                    let neighbor_id = voxels.get_block(
+                        chx + x + x_off, chy + y + y_off, chz + z + z_off);
+                    let neighbor = neighbor_id.config(&voxels);
 
-                    indices.extend(CUBE_INDICES.iter().map(|i| i + vertices.len() as u32));
-                    normals.extend(&CUBE_NORMALS[face]);
-                    vertices.extend(CUBE_VERTICES[face].iter().map(|v| [
+                    let face_vertices = CUBE_VERTICES[face].iter().map(|v| [
                         (v[0] + x as f32) * VOXEL_SIZE,
                         (v[1] + y as f32) * VOXEL_SIZE,
                         (v[2] + z as f32) * VOXEL_SIZE,
-                    ]));
+                    ]);
+
+                    if config.solid && !neighbor.solid {
+                        let base = col_vertices.len() as u32;
+                        col_indices.extend(CUBE_INDICES.chunks(3)
+                            .map(|t| [base + t[0], base + t[1], base + t[2]]));
+                        col_vertices.extend(face_vertices.clone()
+                            .map(|[x, y, z]| Vec3::new(x, y, z)));
+                    }
+
+                    // faces between two different see-through voxels (like
+                    // water against air) still need to be drawn
+                    if !config.render
+                        || (neighbor.render && neighbor.solid)
+                        || neighbor_id == voxel
+                    {
+                        continue;
+                    }
+
+                    let mesh = if config.color.a() < 1.0 {
+                        &mut see_through
+                    } else {
+                        &mut opaque
+                    };
+                    mesh.add_face(face, face_vertices, config.color);
                 }
             }
         }
     }
 
-    (Collider::trimesh(
-        vertices.iter().map(|&[x, y, z]| Vec3::new(x, y, z)).collect(),
-        indices.chunks(3).map(|x| TryInto::<[u32; 3]>::try_into(x).unwrap())
-            .collect()),
-    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            vertices,
-        )
-        .with_inserted_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            normals,
-        )
-        .with_inserted_indices(Indices::U32(indices)))
+    // rapier won't build an empty trimesh
+    let collider = if col_indices.is_empty() {
+        None
+    } else {
+        Some(Collider::trimesh(col_vertices, col_indices))
+    };
+
+    (collider, opaque.build(), see_through.build())
 }
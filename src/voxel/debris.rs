@@ -0,0 +1,336 @@
+use std::collections::BTreeSet;
+
+use bevy::render::mesh::Indices;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::utils::HashSet;
+
+use crate::*;
+use voxel::{voxel_to_world, world_to_voxel, VoxelId, VoxelRes, Voxels};
+use voxel::VOXEL_SIZE;
+use voxel::edit::ProtectedRegions;
+use voxel::mesh_data::*;
+use voxel::sim::NEIGHBORS;
+
+// anything bigger is assumed to be held up somewhere past where we looked
+pub const MAX_ISLAND: usize = 2048;
+
+// debris this slow for this long turns back into voxels
+pub const REST_SPEED: f32 = 0.1;
+pub const REST_TIME: f32 = 0.5;
+
+// how far up a settling voxel looks for a free spot
+pub const MAX_CLIMB: i32 = 4;
+
+// searches go down first, since that's where the ground usually is
+const DOWN_LAST: [IVec3; 6] = [
+    IVec3::Y,
+    IVec3::X,
+    IVec3::Z,
+    IVec3::NEG_X,
+    IVec3::NEG_Z,
+    IVec3::NEG_Y,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Support {
+    Empty,
+    // solid, but only held up by its neighbours
+    Loose,
+    // holds up anything connected to it
+    Anchored,
+}
+
+// finds the solid voxels next to `removed` that are no longer connected to
+// anything anchored. the edge of the loaded world counts as ground, so the
+// closure should anchor voxels in unloaded chunks
+pub fn find_islands(
+    removed: &[IVec3],
+    mut support: impl FnMut(IVec3) -> Support,
+) -> Vec<Vec<IVec3>> {
+    let mut searched = BTreeSet::new();
+    let mut islands = Vec::new();
+
+    for &pos in removed {
+        for n in NEIGHBORS {
+            let start = pos + n;
+            if searched.contains(&start.to_array())
+                || support(start) != Support::Loose
+            {
+                continue;
+            }
+
+            let mut seen = BTreeSet::from([start.to_array()]);
+            let mut stack = vec![start];
+            let mut anchored = false;
+            'search: while let Some(p) = stack.pop() {
+                for n in DOWN_LAST {
+                    let next = p + n;
+                    if seen.contains(&next.to_array()) {
+                        continue;
+                    }
+
+                    // only anchored voxels are left in `searched`, since
+                    // an island would have reached this one already
+                    if searched.contains(&next.to_array()) {
+                        anchored = true;
+                        break 'search;
+                    }
+
+                    match support(next) {
+                        Support::Empty => (),
+                        Support::Anchored => {
+                            anchored = true;
+                            break 'search;
+                        }
+                        Support::Loose => {
+                            seen.insert(next.to_array());
+                            stack.push(next);
+                        }
+                    }
+                }
+
+                if seen.len() > MAX_ISLAND {
+                    anchored = true;
+                    break;
+                }
+            }
+
+            if !anchored {
+                islands.push(seen.iter().copied().map(IVec3::from_array)
+                    .collect());
+            }
+            searched.extend(seen);
+        }
+    }
+    islands
+}
+
+// voxels knocked loose from the terrain, simulated as one body until they
+// come to rest
+#[derive(Component, Clone, Debug)]
+pub struct VoxelDebris {
+    // relative to the first voxel
+    pub blocks: Vec<(IVec3, VoxelId)>,
+    // the body's centre, relative to the centre of the first voxel
+    pub pivot: Vec3,
+    resting: f32,
+}
+
+impl VoxelDebris {
+    // takes voxels in world space, giving back the debris and where its
+    // centre is in the world
+    pub fn from_voxels(voxels: &[(IVec3, VoxelId)]) -> Option<(Self, Vec3)> {
+        let &(origin, _) = voxels.first()?;
+        let blocks: Vec<_> = voxels.iter()
+            .map(|&(pos, id)| (pos - origin, id))
+            .collect();
+        let sum: Vec3 = blocks.iter().map(|(b, _)| b.as_vec3()).sum();
+        let pivot = sum / blocks.len() as f32 * VOXEL_SIZE;
+
+        let debris = VoxelDebris { blocks, pivot, resting: 0.0 };
+        Some((debris, voxel_to_world(origin) + pivot))
+    }
+
+    // the centre of a block, relative to the body
+    pub fn local_position(&self, block: IVec3) -> Vec3 {
+        block.as_vec3() * VOXEL_SIZE - self.pivot
+    }
+
+    pub fn collider(&self) -> Collider {
+        let half = VOXEL_SIZE / 2.0;
+        let corners: Vec<_> = self.blocks.iter()
+            .flat_map(|&(b, _)| {
+                let centre = self.local_position(b);
+                CUBE_VERTICES.iter().flatten()
+                    .map(move |v| centre + (Vec3::from_array(*v) - 0.5)
+                        * VOXEL_SIZE)
+            })
+            .collect();
+
+        Collider::convex_hull(&corners)
+            .unwrap_or_else(|| Collider::cuboid(half, half, half))
+    }
+
+    // only the faces that aren't against another block of the debris
+    pub fn mesh(&self, voxels: &Voxels) -> Mesh {
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut colors: Vec<[f32; 4]> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+
+        let filled: HashSet<_> = self.blocks.iter().map(|&(b, _)| b).collect();
+        for &(b, id) in self.blocks.iter() {
+            let centre = self.local_position(b);
+            let color = id.config(voxels).color.as_linear_rgba_f32();
+            for (face, n) in NEIGHBORS.iter().enumerate() {
+                if filled.contains(&(b + *n)) {
+                    continue;
+                }
+
+                indices.extend(CUBE_INDICES.iter()
+                    .map(|i| i + vertices.len() as u32));
+                normals.extend(&CUBE_NORMALS[face]);
+                colors.extend([color; 4]);
+                vertices.extend(CUBE_VERTICES[face].iter().map(|v| {
+                    (centre + (Vec3::from_array(*v) - 0.5) * VOXEL_SIZE)
+                        .to_array()
+                }));
+            }
+        }
+
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+
+    // where each block goes back into the world: the first empty cell at
+    // or above where it's ended up, stacked on whatever got there first.
+    // `empty` should only allow air, so water and the like aren't written
+    // over. none at all if any block has nowhere to go, so nothing is lost
+    pub fn placement(
+        &self,
+        trans: &GlobalTransform,
+        mut empty: impl FnMut(IVec3) -> bool,
+    ) -> Option<Vec<(IVec3, VoxelId)>> {
+        let mut blocks: Vec<_> = self.blocks.iter()
+            .map(|&(b, id)| {
+                let pos = trans.transform_point(self.local_position(b));
+                (world_to_voxel(pos), id)
+            })
+            .collect();
+        blocks.sort_by_key(|(pos, _)| (pos.y, pos.x, pos.z));
+
+        let mut taken = HashSet::new();
+        blocks.into_iter()
+            .map(|(pos, id)| {
+                let free = (0..MAX_CLIMB)
+                    .map(|i| pos + IVec3::Y * i)
+                    .find(|&p| !taken.contains(&p) && empty(p))?;
+                taken.insert(free);
+                Some((free, id))
+            })
+            .collect()
+    }
+}
+
+// `voxels` are in world space
+pub fn debris_bundle(voxels: &[(IVec3, VoxelId)]) -> Option<impl Bundle> {
+    let (debris, centre) = VoxelDebris::from_voxels(voxels)?;
+    Some((
+        TransformBundle::from_transform(Transform::from_translation(centre)),
+        debris.collider(),
+        debris,
+        RigidBody::Dynamic,
+        Velocity::default(),
+    ))
+}
+
+// drops any terrain left floating by voxels being removed
+pub(super) fn break_off_islands(
+    mut commands: Commands,
+    voxels: Res<VoxelRes>,
+    protected: Res<ProtectedRegions>,
+) {
+    let Ok(mut voxels) = voxels.write()
+    else {
+        return;
+    };
+
+    let removed = voxels.take_removed();
+    if removed.is_empty() {
+        return;
+    }
+
+    let islands = find_islands(&removed, |p| {
+        if !voxels.has_chunk_at(p) {
+            return Support::Anchored;
+        }
+        if !voxels.get_block(p.x, p.y, p.z).config(&voxels).solid {
+            return Support::Empty;
+        }
+        if protected.is_protected(p) {
+            Support::Anchored
+        } else {
+            Support::Loose
+        }
+    });
+
+    for island in islands {
+        let blocks: Vec<_> = island.iter()
+            .map(|&p| (p, voxels.get_block(p.x, p.y, p.z)))
+            .collect();
+        for &(p, _) in blocks.iter() {
+            voxels.edit_block(p.x, p.y, p.z, VoxelId::air());
+        }
+
+        if let Some(debris) = debris_bundle(&blocks) {
+            commands.spawn(debris);
+        }
+    }
+
+    // whatever was next to the islands was part of them, so clearing them
+    // out can't leave anything else floating
+    voxels.take_removed();
+}
+
+pub(super) fn settle_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    voxels: Res<VoxelRes>,
+    mut debris: Query<(Entity, &mut VoxelDebris, &GlobalTransform,
+        &Velocity, Option<&ExternalForce>)>,
+) {
+    let mut settled = Vec::new();
+    for (entity, mut d, _, vel, force) in debris.iter_mut() {
+        // something pushing it around, like a magnet, keeps it loose
+        let pushed = force.is_some_and(|f| f.force != Vec3::ZERO);
+        let still = vel.linvel.length() < REST_SPEED
+            && vel.angvel.length() < REST_SPEED;
+        if pushed || !still {
+            if d.resting != 0.0 {
+                d.resting = 0.0;
+            }
+            continue;
+        }
+
+        d.resting += time.delta_seconds();
+        if d.resting >= REST_TIME {
+            settled.push(entity);
+        }
+    }
+
+    if settled.is_empty() {
+        return;
+    }
+
+    let Ok(mut voxels) = voxels.write()
+    else {
+        return;
+    };
+
+    for entity in settled {
+        let Ok((_, mut d, trans, _, _)) = debris.get_mut(entity)
+        else {
+            continue;
+        };
+        let placement = d.placement(trans, |p| {
+            voxels.has_chunk_at(p)
+                && voxels.get_block(p.x, p.y, p.z) == VoxelId::air()
+        });
+
+        // wedged in somewhere without room, so it stays loose for now and
+        // tries again once it's been still for a while longer
+        let Some(blocks) = placement
+        else {
+            d.resting = 0.0;
+            continue;
+        };
+        for (p, id) in blocks {
+            voxels.edit_block(p.x, p.y, p.z, id);
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,148 @@
+use crate::*;
+use voxel::{voxel_to_world, world_to_voxel, VoxelId, VoxelRes, Voxels};
+use voxel::VOXEL_SIZE;
+use voxel::components::ConstructChunkMesh;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VoxelShape {
+    Sphere { radius: f32 },
+    Cube { half_extent: i32 },
+    // a vertical cylinder going up from the centre
+    Column { radius: f32, height: i32 },
+}
+
+// the biggest shapes spell files can ask for, in voxels. edits walk every
+// cell of the shape while holding the voxel lock
+pub const MAX_SHAPE_RADIUS: f32 = 16.0;
+pub const MAX_SHAPE_HEIGHT: i32 = 32;
+
+impl VoxelShape {
+    // false for nan and infinite sizes too
+    pub fn in_bounds(self) -> bool {
+        let radius = |r: f32| (0.0..=MAX_SHAPE_RADIUS).contains(&r);
+        match self {
+            VoxelShape::Sphere { radius: r } => radius(r),
+            VoxelShape::Cube { half_extent } =>
+                (0..=MAX_SHAPE_RADIUS as i32).contains(&half_extent),
+            VoxelShape::Column { radius: r, height } =>
+                radius(r) && (0..=MAX_SHAPE_HEIGHT).contains(&height),
+        }
+    }
+
+    // every voxel offset from the centre covered by the shape, in a fixed
+    // order so edits come out the same everywhere
+    pub fn offsets(self) -> Vec<IVec3> {
+        let mut offsets = Vec::new();
+        match self {
+            VoxelShape::Sphere { radius } => {
+                let r = radius.ceil() as i32;
+                for x in -r..=r {
+                    for y in -r..=r {
+                        for z in -r..=r {
+                            let off = IVec3::new(x, y, z);
+                            if off.as_vec3().length() <= radius {
+                                offsets.push(off);
+                            }
+                        }
+                    }
+                }
+            }
+
+            VoxelShape::Cube { half_extent: h } => {
+                for x in -h..=h {
+                    for y in -h..=h {
+                        for z in -h..=h {
+                            offsets.push(IVec3::new(x, y, z));
+                        }
+                    }
+                }
+            }
+
+            VoxelShape::Column { radius, height } => {
+                let r = radius.ceil() as i32;
+                for x in -r..=r {
+                    for z in -r..=r {
+                        if Vec2::new(x as f32, z as f32).length() > radius {
+                            continue;
+                        }
+                        for y in 0..height {
+                            offsets.push(IVec3::new(x, y, z));
+                        }
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProtectedRegion {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl ProtectedRegion {
+    pub fn contains(&self, pos: IVec3) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+}
+
+// voxels inside these regions can't be changed by spells
+#[derive(Resource, Clone, Debug, Default)]
+pub struct ProtectedRegions(pub Vec<ProtectedRegion>);
+
+impl ProtectedRegions {
+    pub fn is_protected(&self, pos: IVec3) -> bool {
+        self.0.iter().any(|r| r.contains(pos))
+    }
+}
+
+// breaks the voxels within `radius` of `center` that are softer than the
+// power left at their distance, which falls off to nothing at the edge.
+// returns how many broke
+pub fn blast(
+    voxels: &mut Voxels,
+    protected: &ProtectedRegions,
+    center: Vec3,
+    radius: f32,
+    power: f32,
+) -> usize {
+    let c = world_to_voxel(center);
+    let shape = VoxelShape::Sphere { radius: radius / VOXEL_SIZE };
+
+    let mut broken = 0;
+    for off in shape.offsets() {
+        let pos = c + off;
+        let id = voxels.get_block(pos.x, pos.y, pos.z);
+        if id == VoxelId::air() || protected.is_protected(pos) {
+            continue;
+        }
+
+        let distance = voxel_to_world(pos).distance(center);
+        let left = power * (1.0 - distance / radius);
+        if id.config(voxels).hardness >= left {
+            continue;
+        }
+
+        if voxels.edit_block(pos.x, pos.y, pos.z, VoxelId::air()) {
+            broken += 1;
+        }
+    }
+    broken
+}
+
+// all the edits made in a frame end up as one remesh per touched chunk
+pub(super) fn flush_dirty_chunks(
+    voxels: Res<VoxelRes>,
+    mut remesh: EventWriter<ConstructChunkMesh>,
+) {
+    let Ok(mut voxels) = voxels.try_write()
+    else {
+        return;
+    };
+
+    for (x, y, z) in voxels.take_dirty_chunks() {
+        remesh.send(ConstructChunkMesh::new(x, y, z));
+    }
+}
@@ -1,13 +1,21 @@
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex, RwLock};
 
-use bevy::utils::{HashMap, hashbrown::hash_map::Entry};
+use bevy::utils::{HashMap, HashSet, hashbrown::hash_map::Entry};
 use components::*;
 use crossbeam_channel::{Receiver, Sender};
 
 use crate::*;
+use crate::hash::StableHasher;
+use crate::magic::{ManaColor, MagicElement};
 
 pub mod components;
+pub mod debris;
+pub mod edit;
 mod mesh_data;
+pub mod path;
+pub mod sim;
 
 pub const VOXEL_SIZE: f32 = 0.5;
 pub const CHUNK_SIZE: usize = 32;
@@ -39,6 +47,15 @@ pub struct Voxels {
     voxel_names: HashMap<String, VoxelId>,
     next_id: VoxelId,
     loaded_chunk_mark: bool,
+    dirty: HashSet<(i32, i32, i32)>,
+    // chunks the reaction simulation should look at, kept sorted so every
+    // machine steps them in the same order
+    active: BTreeSet<(i32, i32, i32)>,
+    // solid voxels removed since the last check for unsupported terrain
+    removed: BTreeSet<[i32; 3]>,
+    // bumped whenever a chunk's voxels change, so anything worked out from
+    // them can tell when it's stale
+    versions: HashMap<(i32, i32, i32), u64>,
 }
 
 impl Default for Voxels {
@@ -51,6 +68,9 @@ impl Default for Voxels {
                     render: false,
                     solid: false,
                     color: Color::rgba_u8(0, 0, 0, 0),
+                    mana_color: None,
+                    element: None,
+                    hardness: 0.0,
                 }],
             voxel_names: {
                 let mut map = HashMap::new();
@@ -59,6 +79,10 @@ impl Default for Voxels {
             },
             next_id: VoxelId(1),
             loaded_chunk_mark: false,
+            dirty: HashSet::new(),
+            active: BTreeSet::new(),
+            removed: BTreeSet::new(),
+            versions: HashMap::new(),
         }
     }
 }
@@ -106,10 +130,33 @@ impl Voxels {
         true
     }
 
+    // swaps in a whole chunk's worth of voxels, like a freshly generated one
+    pub fn fill_chunk(
+        &mut self,
+        x: i32,
+        y: i32,
+        z: i32,
+        voxels: Box<[VoxelId; CHUNK_SIZE_CB]>,
+    ) {
+        if let Some(chunk) = self.chunks.get_mut(&(x, y, z)) {
+            chunk.voxels = voxels;
+            *self.versions.entry((x, y, z)).or_default() += 1;
+        }
+    }
+
     pub fn has_chunk(&self, x: i32, y: i32, z: i32) -> bool {
         self.chunks.contains_key(&(x, y, z))
     }
 
+    // whether the chunk holding the voxel at `pos` is loaded
+    pub fn has_chunk_at(&self, pos: IVec3) -> bool {
+        self.has_chunk(
+            pos.x.div_euclid(CHUNK_SIZE_I32),
+            pos.y.div_euclid(CHUNK_SIZE_I32),
+            pos.z.div_euclid(CHUNK_SIZE_I32),
+        )
+    }
+
     pub fn get_chunk(&self, x: i32, y: i32, z: i32) -> Option<&ChunkVoxels> {
         self.chunks.get(&(x, y, z))
     }
@@ -135,6 +182,90 @@ impl Voxels {
         }
     }
 
+    // like set_block, but marks the chunk (and any neighbour sharing the
+    // face) for remeshing. returns whether anything changed
+    pub fn edit_block(&mut self, x: i32, y: i32, z: i32, id: VoxelId) -> bool {
+        let (i, j, k) = (
+            x.div_euclid(CHUNK_SIZE_I32),
+            y.div_euclid(CHUNK_SIZE_I32),
+            z.div_euclid(CHUNK_SIZE_I32),
+        );
+        let old = self.get_block(x, y, z);
+        if !self.has_chunk(i, j, k) || old == id {
+            return false;
+        }
+
+        if old.config(self).solid && !id.config(self).solid {
+            self.removed.insert([x, y, z]);
+        }
+        self.set_block(x, y, z, id);
+        let mut touched = vec![(i, j, k)];
+
+        let (lx, ly, lz) = (
+            x.rem_euclid(CHUNK_SIZE_I32),
+            y.rem_euclid(CHUNK_SIZE_I32),
+            z.rem_euclid(CHUNK_SIZE_I32),
+        );
+        for (local, off) in [
+            (lx, (1, 0, 0)),
+            (ly, (0, 1, 0)),
+            (lz, (0, 0, 1)),
+        ] {
+            if local == 0 {
+                touched.push((i - off.0, j - off.1, k - off.2));
+            } else if local == CHUNK_SIZE_I32 - 1 {
+                touched.push((i + off.0, j + off.1, k + off.2));
+            }
+        }
+
+        for chunk in touched {
+            self.dirty.insert(chunk);
+            self.active.insert(chunk);
+            *self.versions.entry(chunk).or_default() += 1;
+        }
+
+        true
+    }
+
+    // wakes a chunk up for the reaction simulation
+    pub fn activate_chunk(&mut self, x: i32, y: i32, z: i32) {
+        if self.has_chunk(x, y, z) {
+            self.active.insert((x, y, z));
+        }
+    }
+
+    pub fn is_active(&self, x: i32, y: i32, z: i32) -> bool {
+        self.active.contains(&(x, y, z))
+    }
+
+    pub fn take_dirty_chunks(&mut self) -> Vec<(i32, i32, i32)> {
+        let mut dirty: Vec<_> = self.dirty.drain()
+            .filter(|&(x, y, z)| self.chunks.contains_key(&(x, y, z)))
+            .collect();
+        dirty.sort();
+        dirty
+    }
+
+    // whether nothing solid is in the way between two points. the voxels
+    // at either end don't count
+    pub fn line_of_sight(&self, from: Vec3, to: Vec3) -> bool {
+        let ray = voxel_ray(from, to);
+        !ray.iter()
+            .skip(1)
+            .take(ray.len().saturating_sub(2))
+            .any(|p| self.get_block(p.x, p.y, p.z).config(self).solid)
+    }
+
+    pub fn chunk_version(&self, chunk: (i32, i32, i32)) -> u64 {
+        self.versions.get(&chunk).copied().unwrap_or_default()
+    }
+
+    pub fn take_removed(&mut self) -> Vec<IVec3> {
+        std::mem::take(&mut self.removed).into_iter()
+            .map(IVec3::from_array)
+            .collect()
+    }
+
     pub fn get_block(&self, x: i32, y: i32, z: i32) -> VoxelId {
         let (i, j, k) = (
             x.div_euclid(CHUNK_SIZE as i32),
@@ -174,6 +305,21 @@ impl Voxels {
         self.voxel_names.insert(name.to_owned(), id);
         id
     }
+
+    // ids are handed out in registration order and sent over the wire, so
+    // both sides have to register the same voxels in the same order
+    pub fn registry_hash(&self) -> u64 {
+        let mut names: Vec<_> = self.voxel_names.iter().collect();
+        names.sort_by_key(|(_, id)| id.0);
+
+        let mut hasher = StableHasher::default();
+        for (name, id) in names {
+            let config = id.config(self);
+            (name, id.0, config.render, config.solid).hash(&mut hasher);
+            config.hardness.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
 }
 
 #[derive(Resource, Default, Deref)]
@@ -207,6 +353,45 @@ pub struct VoxelConfigEntry {
     pub render: bool,
     pub solid: bool,
     pub color: Color,
+    // casters of this colour regenerate faster near the voxel
+    pub mana_color: Option<ManaColor>,
+    // what the voxel counts as when reacting with its neighbours
+    pub element: Option<MagicElement>,
+    // how much power it takes for an explosion to break the voxel
+    pub hardness: f32,
+}
+
+pub fn register_default_voxels(voxels: &mut Voxels) {
+    let voxel = |name: &str, solid, color, mana_color, element, hardness|
+        VoxelConfigEntry {
+            debug_name: name.to_owned(),
+            render: true,
+            solid,
+            color,
+            mana_color,
+            element,
+            hardness,
+        };
+
+    use MagicElement::*;
+    voxels.add_voxel("solid", voxel("solid", true,
+        Color::rgb_u8(255, 255, 0), Some(ManaColor::Red), Some(Earth), 1.0));
+    voxels.add_voxel("stone", voxel("stone", true,
+        Color::rgb_u8(120, 120, 120), Some(ManaColor::Red), Some(Earth), 3.0));
+    voxels.add_voxel("water", voxel("water", false,
+        Color::rgba_u8(40, 90, 220, 160), Some(ManaColor::Blue), Some(Water),
+        0.5));
+    voxels.add_voxel("ice", voxel("ice", true,
+        Color::rgb_u8(180, 230, 255), Some(ManaColor::Blue), Some(Ice), 1.5));
+    voxels.add_voxel("plant", voxel("plant", true,
+        Color::rgb_u8(60, 170, 50), None, Some(Plant), 0.5));
+    voxels.add_voxel("metal", voxel("metal", true,
+        Color::rgb_u8(170, 170, 180), Some(ManaColor::Yellow), Some(Metal),
+        6.0));
+    voxels.add_voxel("lava", voxel("lava", false,
+        Color::rgb_u8(255, 90, 20), Some(ManaColor::Red), Some(Lava), 0.5));
+    voxels.add_voxel("fire", voxel("fire", false,
+        Color::rgb_u8(255, 160, 30), None, Some(Fire), 0.0));
 }
 
 fn setup_voxels(mut commands: Commands) {
@@ -341,10 +526,8 @@ fn setup_multithreaded<G: ChunkGenerator>(
             }
 
             drop(v);
-            let mut voxels = voxels.write().unwrap();
-            voxels.chunks.get_mut(&(x, y, z)).unwrap().voxels = chunk;
+            voxels.write().unwrap().fill_chunk(x, y, z, chunk);
 
-            drop(voxels);
             tx.send(ConstructChunkMesh { x, y, z }).unwrap();
         }
     });
@@ -361,8 +544,17 @@ impl<G: ChunkGenerator> Plugin for VoxelPlugin<G> {
             .insert_resource(GenRes(Some(g)))
             .add_systems(PreStartup, setup_voxels)
             .add_systems(Startup, setup_multithreaded::<G>)
+            .init_resource::<edit::ProtectedRegions>()
+            .init_resource::<sim::VoxelSim>()
+            .init_resource::<path::PathCache>()
+            .add_systems(FixedUpdate, (
+                sim::run_simulation,
+                debris::break_off_islands,
+                debris::settle_debris,
+            ).chain())
             .add_systems(Update, (
                 load_chunks,
+                edit::flush_dirty_chunks,
                 init_chunk_construction,
                 handle_chunk_mesh_update,
                 middle_man,
@@ -371,6 +563,51 @@ impl<G: ChunkGenerator> Plugin for VoxelPlugin<G> {
     }
 }
 
+pub fn world_to_voxel(pos: Vec3) -> IVec3 {
+    (pos / VOXEL_SIZE).floor().as_ivec3()
+}
+
+// the centre of the voxel at `pos`
+pub fn voxel_to_world(pos: IVec3) -> Vec3 {
+    (pos.as_vec3() + Vec3::splat(0.5)) * VOXEL_SIZE
+}
+
+// every voxel a straight line from `from` to `to` passes through, in order
+pub fn voxel_ray(from: Vec3, to: Vec3) -> Vec<IVec3> {
+    let (a, b) = (from / VOXEL_SIZE, to / VOXEL_SIZE);
+    let mut pos = a.floor().as_ivec3();
+    let end = b.floor().as_ivec3();
+    let dir = b - a;
+
+    let step = [0, 1, 2].map(|i| {
+        if dir[i] > 0.0 { 1 } else if dir[i] < 0.0 { -1 } else { 0 }
+    });
+    let delta = [0, 1, 2].map(|i| (1.0 / dir[i]).abs());
+    // how far along the line it crosses into the next voxel on each axis
+    let mut next = [0, 1, 2].map(|i| match step[i] {
+        1 => (pos[i] as f32 + 1.0 - a[i]) / dir[i],
+        -1 => (pos[i] as f32 - a[i]) / dir[i],
+        _ => f32::INFINITY,
+    });
+
+    let mut ray = vec![pos];
+    while pos != end {
+        // axes already level with the end are skipped, so rounding can't
+        // send the ray past it
+        let Some(axis) = (0..3)
+            .filter(|&i| pos[i] != end[i])
+            .min_by(|&i, &j| next[i].total_cmp(&next[j]))
+        else {
+            break;
+        };
+
+        pos[axis] += step[axis];
+        next[axis] += delta[axis];
+        ray.push(pos);
+    }
+    ray
+}
+
 pub fn set_chunk_voxel(
     chunk: &mut [VoxelId; CHUNK_SIZE_CB],
     x: i32,
@@ -0,0 +1,301 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+
+use bevy::utils::HashMap;
+
+use crate::*;
+use voxel::{voxel_to_world, world_to_voxel, Voxels, CHUNK_SIZE_I32};
+use voxel::VOXEL_SIZE;
+
+// the same as the character controllers' autostep
+pub const STEP_HEIGHT: f32 = 0.51;
+// the furthest agents will drop off a ledge
+pub const MAX_FALL: f32 = 4.0;
+
+// searches give up after reaching this many cells
+pub const MAX_SEARCH: usize = 20_000;
+pub const MAX_CACHED: usize = 256;
+
+// waypoints count as reached this close, horizontally
+pub const ARRIVE_DISTANCE: f32 = 0.3;
+
+// move costs, in tenths of a voxel
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+const CLIMB: u32 = 5;
+
+const DIRECTIONS: [IVec3; 8] = [
+    IVec3::new(1, 0, 0),
+    IVec3::new(0, 0, 1),
+    IVec3::new(-1, 0, 0),
+    IVec3::new(0, 0, -1),
+    IVec3::new(1, 0, 1),
+    IVec3::new(-1, 0, 1),
+    IVec3::new(-1, 0, -1),
+    IVec3::new(1, 0, -1),
+];
+
+pub trait Terrain {
+    fn solid(&self, pos: IVec3) -> bool;
+}
+
+impl Terrain for Voxels {
+    fn solid(&self, pos: IVec3) -> bool {
+        self.get_block(pos.x, pos.y, pos.z).config(self).solid
+    }
+}
+
+// the size of whatever's walking, in voxels
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Agent {
+    pub height: i32,
+    pub step: i32,
+    pub max_fall: i32,
+}
+
+impl Agent {
+    // takes sizes in world units
+    pub fn new(height: f32, step: f32, max_fall: f32) -> Agent {
+        Agent {
+            height: (height / VOXEL_SIZE).ceil().max(1.0) as i32,
+            step: (step / VOXEL_SIZE).floor() as i32,
+            max_fall: (max_fall / VOXEL_SIZE).floor() as i32,
+        }
+    }
+
+    // whether there's room for it with its feet in `pos`
+    fn fits(self, t: &impl Terrain, pos: IVec3) -> bool {
+        (0..self.height).all(|y| !t.solid(pos + IVec3::Y * y))
+    }
+
+    pub fn can_stand(self, t: &impl Terrain, pos: IVec3) -> bool {
+        t.solid(pos - IVec3::Y) && self.fits(t, pos)
+    }
+
+    // where it lands after stepping off into `pos`, unless it's too far down
+    fn land(self, t: &impl Terrain, pos: IVec3) -> Option<IVec3> {
+        (0..=self.max_fall)
+            .map(|d| pos - IVec3::Y * d)
+            .take_while(|&p| self.fits(t, p))
+            .find(|&p| t.solid(p - IVec3::Y))
+    }
+
+    // the cell it's standing in with its feet at `feet`, if it's on the
+    // ground or about to land on it
+    pub fn standing_cell(self, t: &impl Terrain, feet: Vec3) -> Option<IVec3> {
+        let cell = world_to_voxel(feet);
+        [cell, cell + IVec3::Y].into_iter()
+            .find(|&p| self.can_stand(t, p))
+            .or_else(|| self.land(t, cell))
+    }
+
+    // every cell it can get to in one move from `pos`, with what it costs
+    fn moves(self, t: &impl Terrain, pos: IVec3) -> Vec<(IVec3, u32)> {
+        let mut moves = Vec::new();
+        for d in DIRECTIONS {
+            // no cutting corners
+            let diagonal = d.x != 0 && d.z != 0;
+            if diagonal && !(self.fits(t, pos + IVec3::new(d.x, 0, 0))
+                && self.fits(t, pos + IVec3::new(0, 0, d.z)))
+            {
+                continue;
+            }
+
+            let next = pos + d;
+            let cost = if diagonal { DIAGONAL } else { STRAIGHT };
+            if self.fits(t, next) {
+                if let Some(to) = self.land(t, next) {
+                    moves.push((to, cost + (next.y - to.y) as u32));
+                }
+                continue;
+            }
+
+            if diagonal {
+                continue;
+            }
+            for up in 1..=self.step {
+                if !self.fits(t, pos + IVec3::Y * up) {
+                    break;
+                }
+                let to = next + IVec3::Y * up;
+                if self.can_stand(t, to) {
+                    moves.push((to, cost + CLIMB * up as u32));
+                    break;
+                }
+            }
+        }
+        moves
+    }
+}
+
+// an A* search from `start` to `goal`, both cells the agent is standing in.
+// the path includes both ends
+pub fn find_path(
+    t: &impl Terrain,
+    agent: Agent,
+    start: IVec3,
+    goal: IVec3,
+) -> Option<Vec<IVec3>> {
+    if !agent.can_stand(t, start) || !agent.can_stand(t, goal) {
+        return None;
+    }
+
+    // octile distance, ignoring height since falling is nearly free
+    let estimate = |p: IVec3| {
+        let d = (goal - p).abs();
+        let (long, short) = (d.x.max(d.z) as u32, d.x.min(d.z) as u32);
+        STRAIGHT * (long - short) + DIAGONAL * short
+    };
+
+    let mut open = BinaryHeap::new();
+    let mut costs = HashMap::new();
+    let mut came_from = HashMap::new();
+    open.push(Reverse((estimate(start), 0, start.to_array())));
+    costs.insert(start, 0);
+
+    while let Some(Reverse((_, cost, pos))) = open.pop() {
+        let pos = IVec3::from_array(pos);
+        if pos == goal {
+            let mut path = vec![goal];
+            while let Some(&prev) = came_from.get(path.last().unwrap()) {
+                path.push(prev);
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        if costs.get(&pos).is_some_and(|&c| cost > c) {
+            continue;
+        }
+        if costs.len() > MAX_SEARCH {
+            return None;
+        }
+
+        for (next, step) in agent.moves(t, pos) {
+            let cost = cost + step;
+            if costs.get(&next).is_some_and(|&c| c <= cost) {
+                continue;
+            }
+            costs.insert(next, cost);
+            came_from.insert(next, pos);
+            open.push(Reverse((cost + estimate(next), cost, next.to_array())));
+        }
+    }
+    None
+}
+
+// the chunks a path relies on, and their versions when it was found
+type ChunkVersions = Vec<((i32, i32, i32), u64)>;
+
+fn chunk_versions(
+    voxels: &Voxels,
+    agent: Agent,
+    path: &[IVec3],
+) -> ChunkVersions {
+    let chunks: BTreeSet<_> = path.iter()
+        .flat_map(|&p| (-1..=agent.height).map(move |y| p + IVec3::Y * y))
+        .map(|p| (
+            p.x.div_euclid(CHUNK_SIZE_I32),
+            p.y.div_euclid(CHUNK_SIZE_I32),
+            p.z.div_euclid(CHUNK_SIZE_I32),
+        ))
+        .collect();
+    chunks.into_iter()
+        .map(|c| (c, voxels.chunk_version(c)))
+        .collect()
+}
+
+fn unchanged(voxels: &Voxels, chunks: &ChunkVersions) -> bool {
+    chunks.iter().all(|&(c, version)| voxels.chunk_version(c) == version)
+}
+
+struct CachedPath {
+    path: Vec<IVec3>,
+    chunks: ChunkVersions,
+}
+
+// paths stay cached until one of the chunks they go through is edited
+#[derive(Resource, Default)]
+pub struct PathCache {
+    paths: HashMap<(IVec3, IVec3, Agent), CachedPath>,
+}
+
+impl PathCache {
+    pub fn find(
+        &mut self,
+        voxels: &Voxels,
+        agent: Agent,
+        start: IVec3,
+        goal: IVec3,
+    ) -> Option<Vec<IVec3>> {
+        let key = (start, goal, agent);
+        if let Some(cached) = self.paths.get(&key) {
+            if unchanged(voxels, &cached.chunks) {
+                return Some(cached.path.clone());
+            }
+        }
+        self.paths.remove(&key);
+
+        let path = find_path(voxels, agent, start, goal)?;
+        if self.paths.len() >= MAX_CACHED {
+            self.paths.clear();
+        }
+        self.paths.insert(key, CachedPath {
+            path: path.clone(),
+            chunks: chunk_versions(voxels, agent, &path),
+        });
+        Some(path)
+    }
+}
+
+// walks something along a path, one waypoint at a time
+#[derive(Component, Clone, Debug, Default)]
+pub struct PathFollower {
+    pub waypoints: Vec<IVec3>,
+    next: usize,
+    chunks: ChunkVersions,
+}
+
+impl PathFollower {
+    pub fn new(waypoints: Vec<IVec3>) -> PathFollower {
+        PathFollower { waypoints, next: 0, chunks: Vec::new() }
+    }
+
+    // remembers the chunks the path goes through, so `unchanged` can tell
+    // when one of them has been edited since
+    pub fn tracked(
+        voxels: &Voxels,
+        agent: Agent,
+        waypoints: Vec<IVec3>,
+    ) -> PathFollower {
+        let chunks = chunk_versions(voxels, agent, &waypoints);
+        PathFollower { waypoints, next: 0, chunks }
+    }
+
+    // always true for paths that weren't made with `tracked`
+    pub fn unchanged(&self, voxels: &Voxels) -> bool {
+        unchanged(voxels, &self.chunks)
+    }
+
+    pub fn goal(&self) -> Option<IVec3> {
+        self.waypoints.last().copied()
+    }
+
+    pub fn done(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+
+    // which way to go from `feet` to reach the next waypoint's floor,
+    // moving on to the one after once it's close enough
+    pub fn steer(&mut self, feet: Vec3) -> Option<Vec3> {
+        while let Some(&cell) = self.waypoints.get(self.next) {
+            let floor = voxel_to_world(cell) - Vec3::Y * VOXEL_SIZE / 2.0;
+            let to = floor - feet;
+            if Vec2::new(to.x, to.z).length() > ARRIVE_DISTANCE {
+                return Some(to.normalize_or_zero());
+            }
+            self.next += 1;
+        }
+        None
+    }
+}
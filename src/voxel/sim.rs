@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::btree_map::Entry;
+use std::hash::{Hash, Hasher};
+
+use crate::*;
+use hash::StableHasher;
+use magic::MagicElement;
+use voxel::{VoxelId, VoxelRes, Voxels, CHUNK_SIZE_I32};
+
+// how many fixed ticks between simulation steps
+pub const SIM_INTERVAL: u32 = 8;
+
+// one in this many chances, per step
+pub const FIRE_SPREAD_CHANCE: u32 = 3;
+pub const FIRE_BURNOUT_CHANCE: u32 = 6;
+pub const PLANT_GROWTH_CHANCE: u32 = 64;
+
+// how many steps a voxel stays charged after electricity passes through it
+pub const CHARGE_STEPS: u8 = 4;
+
+// the most voxels a single discharge can spread through
+pub const MAX_CONDUCTION: usize = 256;
+
+pub const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::Y,
+    IVec3::Z,
+    IVec3::NEG_X,
+    IVec3::NEG_Y,
+    IVec3::NEG_Z,
+];
+
+const SIDES: [IVec3; 4] = [IVec3::X, IVec3::Z, IVec3::NEG_X, IVec3::NEG_Z];
+
+// every step reads the world as it was at the start of the step and writes
+// its changes afterwards, and all randomness comes from hashing the step
+// number and position, so two machines with the same voxels stay in sync
+#[derive(Resource, Default)]
+pub struct VoxelSim {
+    // how many steps have run
+    pub ticks: u64,
+    // fixed ticks seen, a step runs every SIM_INTERVAL of them
+    fixed_ticks: u32,
+    charged: BTreeMap<[i32; 3], u8>,
+}
+
+struct Ids {
+    air: VoxelId,
+    fire: VoxelId,
+    stone: VoxelId,
+    water: VoxelId,
+    plant: VoxelId,
+}
+
+impl VoxelSim {
+    pub fn is_charged(&self, pos: IVec3) -> bool {
+        self.charged.contains_key(&pos.to_array())
+    }
+
+    pub fn charged(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.charged.keys().map(|&p| IVec3::from_array(p))
+    }
+
+    // charges every water or metal voxel connected to `from`, returning how
+    // many were charged
+    pub fn conduct(&mut self, voxels: &Voxels, from: IVec3) -> usize {
+        let conducts = |p: IVec3| matches!(
+            voxels.get_block(p.x, p.y, p.z).config(voxels).element,
+            Some(MagicElement::Water | MagicElement::Metal)
+        );
+
+        let mut queue: VecDeque<_> = std::iter::once(from)
+            .chain(NEIGHBORS.map(|n| from + n))
+            .filter(|&p| conducts(p))
+            .collect();
+        let mut seen: BTreeSet<_> = queue.iter().map(|p| p.to_array()).collect();
+
+        let mut count = 0;
+        while let Some(pos) = queue.pop_front() {
+            self.charged.insert(pos.to_array(), CHARGE_STEPS);
+            count += 1;
+            if count >= MAX_CONDUCTION {
+                break;
+            }
+
+            for n in NEIGHBORS {
+                let next = pos + n;
+                if conducts(next) && seen.insert(next.to_array()) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
+    }
+
+    fn roll(&self, pos: IVec3, salt: u8) -> u32 {
+        let mut hasher = StableHasher::default();
+        (self.ticks, pos.to_array(), salt).hash(&mut hasher);
+        hasher.finish() as u32
+    }
+
+    fn chance(&self, pos: IVec3, salt: u8, one_in: u32) -> bool {
+        self.roll(pos, salt) % one_in == 0
+    }
+
+    // runs one step over the active chunks, returning how many voxels changed
+    pub fn step(&mut self, voxels: &mut Voxels) -> usize {
+        self.ticks += 1;
+        self.charged.retain(|_, steps| {
+            *steps -= 1;
+            *steps > 0
+        });
+
+        let id = |name: &str| voxels.id_from_name(name);
+        let (Some(fire), Some(stone), Some(water), Some(plant)) =
+            (id("fire"), id("stone"), id("water"), id("plant"))
+        else {
+            return 0;
+        };
+        let air = VoxelId::air();
+
+        let mut step = Step {
+            sim: &*self,
+            voxels: &*voxels,
+            ids: Ids { air, fire, stone, water, plant },
+            changes: BTreeMap::new(),
+        };
+
+        let mut busy = BTreeSet::new();
+        for &(cx, cy, cz) in voxels.active.iter() {
+            let mut reacting = false;
+            for x in 0..CHUNK_SIZE_I32 {
+                for y in 0..CHUNK_SIZE_I32 {
+                    for z in 0..CHUNK_SIZE_I32 {
+                        let pos = IVec3::new(
+                            cx * CHUNK_SIZE_I32 + x,
+                            cy * CHUNK_SIZE_I32 + y,
+                            cz * CHUNK_SIZE_I32 + z,
+                        );
+                        match step.element(pos) {
+                            // fire always burns out in the end
+                            Some(MagicElement::Fire) => {
+                                step.burn(pos);
+                                reacting = true;
+                            }
+                            Some(MagicElement::Lava) => step.cool(pos),
+                            Some(MagicElement::Plant) =>
+                                reacting |= step.grow(pos),
+                            Some(MagicElement::Water) => step.flow(pos),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+
+            if reacting {
+                busy.insert((cx, cy, cz));
+            }
+        }
+
+        // chunks with nothing left to do go to sleep until something in
+        // them is edited again, which includes the changes below
+        let changes = step.changes;
+        voxels.active = busy;
+
+        let mut changed = 0;
+        for (pos, id) in changes {
+            if voxels.edit_block(pos[0], pos[1], pos[2], id) {
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+struct Step<'a> {
+    sim: &'a VoxelSim,
+    voxels: &'a Voxels,
+    ids: Ids,
+    changes: BTreeMap<[i32; 3], VoxelId>,
+}
+
+impl Step<'_> {
+    fn block(&self, pos: IVec3) -> VoxelId {
+        self.voxels.get_block(pos.x, pos.y, pos.z)
+    }
+
+    fn element(&self, pos: IVec3) -> Option<MagicElement> {
+        self.block(pos).config(self.voxels).element
+    }
+
+    // the first change to claim a voxel in a step wins
+    fn set(&mut self, pos: IVec3, id: VoxelId) -> bool {
+        match self.changes.entry(pos.to_array()) {
+            Entry::Vacant(v) => {
+                v.insert(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn swap(&mut self, from: IVec3, to: IVec3) -> bool {
+        if !self.voxels.has_chunk_at(to)
+            || self.changes.contains_key(&from.to_array())
+            || self.changes.contains_key(&to.to_array())
+        {
+            return false;
+        }
+
+        let (a, b) = (self.block(from), self.block(to));
+        self.set(from, b);
+        self.set(to, a);
+        true
+    }
+
+    fn burn(&mut self, pos: IVec3) {
+        let wet = NEIGHBORS.iter()
+            .any(|&n| self.element(pos + n) == Some(MagicElement::Water));
+        if wet {
+            self.set(pos, self.ids.air);
+            return;
+        }
+
+        let mut fuel = false;
+        for (i, &n) in NEIGHBORS.iter().enumerate() {
+            if self.element(pos + n) != Some(MagicElement::Plant) {
+                continue;
+            }
+
+            fuel = true;
+            if self.sim.chance(pos + n, i as u8, FIRE_SPREAD_CHANCE) {
+                self.set(pos + n, self.ids.fire);
+            }
+        }
+
+        // fires with nothing left to burn die down quickly
+        let burnout = if fuel { FIRE_BURNOUT_CHANCE } else { 2 };
+        if self.sim.chance(pos, 6, burnout) {
+            self.set(pos, self.ids.air);
+        }
+    }
+
+    fn cool(&mut self, pos: IVec3) {
+        for n in NEIGHBORS {
+            match self.element(pos + n) {
+                Some(MagicElement::Water) => {
+                    self.set(pos, self.ids.stone);
+                }
+                Some(MagicElement::Ice) => {
+                    self.set(pos + n, self.ids.water);
+                }
+                Some(MagicElement::Plant) => {
+                    self.set(pos + n, self.ids.fire);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn flow(&mut self, pos: IVec3) {
+        let below = pos - IVec3::Y;
+        if self.block(below) == self.ids.air {
+            self.swap(pos, below);
+            return;
+        }
+
+        // water only spreads sideways to run off an edge, so pools settle
+        let start = self.sim.roll(pos, 7) as usize;
+        for i in 0..SIDES.len() {
+            let side = pos + SIDES[(start + i) % SIDES.len()];
+            if self.block(side) == self.ids.air
+                && self.block(side - IVec3::Y) == self.ids.air
+                && self.swap(pos, side)
+            {
+                return;
+            }
+        }
+    }
+
+    // plants creep across the ground next to them
+    fn can_grow_into(&self, side: IVec3) -> bool {
+        let ground = self.block(side - IVec3::Y);
+        self.block(side) == self.ids.air
+            && ground.config(self.voxels).solid
+            && ground != self.ids.plant
+    }
+
+    // returns whether the plant still has anywhere to spread to
+    fn grow(&mut self, pos: IVec3) -> bool {
+        if !SIDES.iter().any(|&s| self.can_grow_into(pos + s)) {
+            return false;
+        }
+
+        let side = pos + SIDES[self.sim.roll(pos, 9) as usize % SIDES.len()];
+        if self.sim.chance(pos, 8, PLANT_GROWTH_CHANCE)
+            && self.can_grow_into(side)
+        {
+            self.set(side, self.ids.plant);
+        }
+        true
+    }
+}
+
+pub(super) fn run_simulation(
+    voxels: Res<VoxelRes>,
+    mut sim: ResMut<VoxelSim>,
+) {
+    sim.fixed_ticks += 1;
+    if sim.fixed_ticks % SIM_INTERVAL != 0 {
+        return;
+    }
+
+    // wait for the lock rather than skipping the step, or one machine could
+    // fall a step behind the others
+    let Ok(mut voxels) = voxels.write()
+    else {
+        return;
+    };
+
+    sim.step(&mut voxels);
+}
@@ -0,0 +1,280 @@
+// runs a server and any number of clients as separate apps in one process,
+// talking over crossbeam channels instead of sockets. time is advanced by
+// hand, so tests are deterministic apart from the link conditioner.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::Instant;
+
+use magic_game::*;
+use client::ClientCommands;
+use server::ServerCommands;
+use magic_game::net::client::{
+    client_plugin_with_io, ClientNetPlugin, ServerInfo,
+};
+use magic_game::net::config::NetSettings;
+use magic_game::net::protocol::shared_config;
+use magic_game::net::server::{server_plugin_with_io, ServerNetPlugin};
+use magic_game::net::ProtocolPlugin;
+
+// the server only ever sees the fake address, nothing is bound to it
+pub const HARNESS_SERVER_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+pub struct HarnessConfig {
+    pub clients: usize,
+    pub settings: NetSettings,
+    // applied to packets arriving at both the server and the clients
+    pub conditioner: Option<LinkConditionerConfig>,
+    pub frame_duration: Duration,
+}
+
+impl Default for HarnessConfig {
+    fn default() -> Self {
+        let settings = NetSettings::default();
+        HarnessConfig {
+            clients: 1,
+            frame_duration: settings.tick_duration(),
+            settings,
+            conditioner: None,
+        }
+    }
+}
+
+impl HarnessConfig {
+    pub fn with_clients(mut self, clients: usize) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    pub fn with_conditioner(
+        mut self,
+        latency: Duration,
+        jitter: Duration,
+        loss: f32,
+    ) -> Self {
+        self.conditioner = Some(LinkConditionerConfig {
+            incoming_latency: latency,
+            incoming_jitter: jitter,
+            incoming_loss: loss,
+        });
+        self
+    }
+}
+
+pub struct NetHarness {
+    pub server: App,
+    pub clients: Vec<App>,
+    pub client_ids: Vec<ClientId>,
+    pub frame_duration: Duration,
+    tick_duration: Duration,
+    now: Instant,
+}
+
+impl NetHarness {
+    pub fn new(config: HarnessConfig) -> NetHarness {
+        let shared = shared_config(&config.settings, Mode::Separate);
+        let now = Instant::now();
+
+        let mut channels = Vec::new();
+        let mut clients = Vec::new();
+        let mut client_ids = Vec::new();
+        for i in 0..config.clients {
+            let (to_server, from_client) =
+                crossbeam_channel::unbounded::<Vec<u8>>();
+            let (to_client, from_server) =
+                crossbeam_channel::unbounded::<Vec<u8>>();
+            let client_addr = SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::LOCALHOST), 2 + i as u16);
+            channels.push((client_addr, from_client, to_client));
+
+            let mut settings = config.settings.clone();
+            settings.client.server_addr = HARNESS_SERVER_ADDR;
+            settings.client.client_id = Some(i as u64 + 1);
+            client_ids.push(ClientId::Netcode(i as u64 + 1));
+
+            let mut io = IoConfig::from_transport(
+                ClientTransport::LocalChannel {
+                    recv: from_server,
+                    send: to_server,
+                });
+            if let Some(conditioner) = config.conditioner.clone() {
+                io = io.with_conditioner(conditioner);
+            }
+
+            let mut app = App::new();
+            app
+                .add_plugins(MinimalPlugins)
+                .add_plugins(client_plugin_with_io(
+                    shared.clone(), &settings, io))
+                .add_plugins(ProtocolPlugin)
+                .add_plugins(ClientNetPlugin)
+                .insert_resource(settings)
+                .insert_resource(TimeUpdateStrategy::ManualInstant(now));
+            app.finish();
+            app.cleanup();
+            clients.push(app);
+        }
+
+        let mut io = IoConfig::from_transport(
+            ServerTransport::Channels { channels });
+        if let Some(conditioner) = config.conditioner {
+            io = io.with_conditioner(conditioner);
+        }
+
+        let mut server = App::new();
+        server
+            .add_plugins(MinimalPlugins)
+            .add_plugins(server_plugin_with_io(shared, io))
+            .add_plugins(ProtocolPlugin)
+            .add_plugins(ServerNetPlugin)
+            .insert_resource(config.settings.clone())
+            .insert_resource(TimeUpdateStrategy::ManualInstant(now));
+        server.finish();
+        server.cleanup();
+
+        NetHarness {
+            server,
+            clients,
+            client_ids,
+            frame_duration: config.frame_duration,
+            tick_duration: config.settings.tick_duration(),
+            now,
+        }
+    }
+
+    // starts the server and connects every client, returning whether they
+    // all made it through the handshake within `max_frames`
+    pub fn connect(&mut self, max_frames: usize) -> bool {
+        self.server.world.run_system_once(|mut commands: Commands| {
+            commands.start_server();
+        });
+        for client in self.clients.iter_mut() {
+            client.world.run_system_once(|mut commands: Commands| {
+                commands.connect_client();
+            });
+        }
+
+        self.step_until(max_frames, |h| {
+            h.clients.iter().all(|c| c.world.contains_resource::<ServerInfo>())
+        })
+    }
+
+    pub fn client(&mut self, i: usize) -> &mut App {
+        &mut self.clients[i]
+    }
+
+    pub fn frame_step(&mut self) {
+        self.now += self.frame_duration;
+        let strategy = TimeUpdateStrategy::ManualInstant(self.now);
+
+        self.server.insert_resource(strategy.clone());
+        self.server.update();
+        for client in self.clients.iter_mut() {
+            client.insert_resource(strategy.clone());
+            client.update();
+        }
+    }
+
+    pub fn frame_steps(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.frame_step();
+        }
+    }
+
+    pub fn advance_ticks(&mut self, ticks: usize) {
+        let per_frame = self.frame_duration.as_secs_f64();
+        let wanted = self.tick_duration.as_secs_f64() * ticks as f64;
+        self.frame_steps((wanted / per_frame).ceil() as usize);
+    }
+
+    pub fn step_until(
+        &mut self,
+        max_frames: usize,
+        mut done: impl FnMut(&mut NetHarness) -> bool,
+    ) -> bool {
+        for _ in 0..max_frames {
+            if done(self) {
+                return true;
+            }
+            self.frame_step();
+        }
+        done(self)
+    }
+
+    // records every `M` received from now on, read back with
+    // `server_received` and `client_received`
+    pub fn record_messages<M: Message + Clone>(&mut self) {
+        self.server
+            .init_resource::<ServerReceived<M>>()
+            .add_systems(PostUpdate, record_server_messages::<M>);
+        for client in self.clients.iter_mut() {
+            client
+                .init_resource::<ClientReceived<M>>()
+                .add_systems(PostUpdate, record_client_messages::<M>);
+        }
+    }
+
+    pub fn server_received<M: Message + Clone>(&mut self) -> Vec<(ClientId, M)> {
+        self.server.world.get_resource_mut::<ServerReceived<M>>()
+            .map(|mut r| std::mem::take(&mut r.0))
+            .unwrap_or_default()
+    }
+
+    pub fn client_received<M: Message + Clone>(&mut self, i: usize) -> Vec<M> {
+        self.clients[i].world.get_resource_mut::<ClientReceived<M>>()
+            .map(|mut r| std::mem::take(&mut r.0))
+            .unwrap_or_default()
+    }
+
+    pub fn server_components<C: Component + Clone>(&mut self) -> Vec<C> {
+        components(&mut self.server.world)
+    }
+
+    pub fn client_components<C: Component + Clone>(&mut self, i: usize) -> Vec<C> {
+        components(&mut self.clients[i].world)
+    }
+}
+
+fn components<C: Component + Clone>(world: &mut World) -> Vec<C> {
+    world.query::<&C>().iter(world).cloned().collect()
+}
+
+#[derive(Resource)]
+struct ServerReceived<M>(Vec<(ClientId, M)>);
+
+impl<M> Default for ServerReceived<M> {
+    fn default() -> Self {
+        ServerReceived(Vec::new())
+    }
+}
+
+#[derive(Resource)]
+struct ClientReceived<M>(Vec<M>);
+
+impl<M> Default for ClientReceived<M> {
+    fn default() -> Self {
+        ClientReceived(Vec::new())
+    }
+}
+
+fn record_server_messages<M: Message + Clone>(
+    mut messages: EventReader<server::MessageEvent<M>>,
+    mut received: ResMut<ServerReceived<M>>,
+) {
+    for m in messages.read() {
+        received.0.push((m.context, m.message.clone()));
+    }
+}
+
+fn record_client_messages<M: Message + Clone>(
+    mut messages: EventReader<client::MessageEvent<M>>,
+    mut received: ResMut<ClientReceived<M>>,
+) {
+    for m in messages.read() {
+        received.0.push(m.message.clone());
+    }
+}
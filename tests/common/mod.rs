@@ -0,0 +1,4 @@
+// each test binary only uses some of what's in here
+#![allow(dead_code)]
+
+pub mod harness;
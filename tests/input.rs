@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use bevy::input::keyboard::KeyCode;
+use magic_game::client_plugin::input::{
+    Action, ActionState, Binding, Input, InputSettings};
+
+fn write(name: &str, src: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, src).unwrap();
+    path
+}
+
+#[test]
+fn default_bindings_all_resolve() {
+    let settings = InputSettings::default();
+    for action in Action::ALL {
+        assert!(!settings.bindings(action).is_empty(), "{:?}", action);
+        for binding in settings.bindings(action) {
+            assert!(binding.input().is_some(), "{:?}", binding);
+        }
+    }
+}
+
+#[test]
+fn every_spell_slot_has_a_key() {
+    // the hud numbers the slots from 1, and those number keys cast them
+    let keys = [Action::CastSlot1, Action::CastSlot2, Action::CastSlot3];
+    for (slot, action) in keys.into_iter().enumerate() {
+        assert_eq!(action.cast_slot(), Some(slot));
+        assert_eq!(action.default_bindings()[0],
+            Binding::Key(format!("Digit{}", slot + 1)));
+    }
+    assert_eq!(Action::CastPrimary.cast_slot(), Some(0));
+    assert_eq!(Action::Jump.cast_slot(), None);
+}
+
+#[test]
+fn settings_files_fill_in_defaults() {
+    let path = write("magic-game-input.ron", r#"(
+        bindings: { Jump: [Key("KeyJ")] },
+        invert_y: true,
+    )"#);
+    let settings = InputSettings::load(&path).unwrap();
+    assert!(settings.invert_y);
+    assert_eq!(settings.bindings(Action::Jump),
+        &[Binding::Key("KeyJ".to_owned())]);
+    assert_eq!(settings.bindings(Action::MoveForward),
+        Action::MoveForward.default_bindings().as_slice());
+
+    let missing = std::env::temp_dir().join("magic-game-no-input.ron");
+    assert!(InputSettings::load(&missing).is_ok());
+
+    let bad = write("magic-game-bad-input.ron",
+        r#"(bindings: { Jump: [Key("Spacebar")] })"#);
+    assert!(InputSettings::load(&bad).is_err());
+
+    for speed in ["mouse_sensitivity: NaN", "mouse_sensitivity: 0.0",
+        "gamepad_look_speed: -1.0", "gamepad_look_speed: inf"]
+    {
+        let bad = write("magic-game-bad-speed.ron", &format!("({speed})"));
+        assert!(InputSettings::load(&bad).is_err(), "{speed} was let through");
+    }
+}
+
+#[test]
+fn actions_follow_their_bindings() {
+    let mut settings = InputSettings::default();
+    settings.bindings.insert(Action::Jump,
+        vec![Binding::Key("KeyJ".to_owned())]);
+    let mut actions = ActionState::default();
+
+    let held = |key| move |input| input == Input::Key(key);
+    actions.update(&settings, held(KeyCode::KeyJ));
+    assert!(actions.pressed(Action::Jump));
+    assert!(actions.just_pressed(Action::Jump));
+
+    actions.update(&settings, held(KeyCode::KeyJ));
+    assert!(actions.pressed(Action::Jump));
+    assert!(!actions.just_pressed(Action::Jump));
+
+    // space isn't jump any more, but still flies up
+    actions.update(&settings, held(KeyCode::Space));
+    assert!(!actions.pressed(Action::Jump));
+    assert!(actions.pressed(Action::FlyUp));
+
+    actions.update(&settings, held(KeyCode::KeyW));
+    assert_eq!(actions.movement().y, 1.0);
+}
+
+#[test]
+fn rebinding_keeps_gamepad_buttons() {
+    let mut settings = InputSettings::default();
+    let key = Binding::from_input(Input::Key(KeyCode::KeyK)).unwrap();
+    assert_eq!(key, Binding::Key("KeyK".to_owned()));
+
+    settings.rebind(Action::Jump, key.clone());
+    let bindings = settings.bindings(Action::Jump);
+    assert_eq!(bindings[0], key);
+    assert!(bindings.iter().any(|b| matches!(b, Binding::Gamepad(_))));
+    assert!(!bindings.contains(&Binding::Key("Space".to_owned())));
+}
+
+#[test]
+fn settings_save_and_load() {
+    use magic_game::client_plugin::settings::VideoSettings;
+
+    let mut input = InputSettings::default();
+    input.rebind(Action::Crouch, Binding::Key("KeyC".to_owned()));
+    input.invert_y = true;
+    let path = std::env::temp_dir().join("magic-game-saved-input.ron");
+    input.save(&path).unwrap();
+    let loaded = InputSettings::load(&path).unwrap();
+    assert_eq!(loaded.bindings, input.bindings);
+    assert!(loaded.invert_y);
+
+    let video = VideoSettings {
+        render_distance: 4,
+        show_fps: false,
+        ..VideoSettings::default()
+    };
+    let path = std::env::temp_dir().join("magic-game-saved-video.ron");
+    video.save(&path).unwrap();
+    assert_eq!(VideoSettings::load(&path).unwrap(), video);
+
+    let bad = write("magic-game-bad-video.ron", "(render_distance: 100)");
+    assert!(VideoSettings::load(&bad).is_err());
+}
+
+#[test]
+fn menu_settings_stay_in_range() {
+    use magic_game::client_plugin::menu::Setting;
+    use magic_game::client_plugin::settings::{
+        VideoSettings, MAX_RENDER_DISTANCE, MIN_FOV};
+
+    let mut video = VideoSettings::default();
+    let mut input = InputSettings::default();
+
+    Setting::RenderDistance.adjust(100, &mut video, &mut input);
+    assert_eq!(video.render_distance, MAX_RENDER_DISTANCE);
+    Setting::Fov.adjust(-100, &mut video, &mut input);
+    assert_eq!(video.fov_degrees, MIN_FOV);
+
+    let sensitivity = input.mouse_sensitivity;
+    Setting::Sensitivity.adjust(1, &mut video, &mut input);
+    assert!(input.mouse_sensitivity > sensitivity);
+
+    let fps = video.show_fps;
+    Setting::ShowFps.adjust(1, &mut video, &mut input);
+    assert_ne!(video.show_fps, fps);
+    assert!(Setting::ShowFps.label(&video, &input).starts_with("FPS"));
+}
@@ -0,0 +1,421 @@
+use magic_game::magic::{ManaColor, MagicElement};
+use magic_game::magic::components::MagicCaster;
+
+#[test]
+fn every_color_pair_has_an_element() {
+    use ManaColor::*;
+    use MagicElement::*;
+
+    let table = [
+        ((Black, Black), NonElemental),
+        ((Black, Red), Earth),
+        ((Black, Yellow), Electricity),
+        ((Black, Blue), Water),
+
+        ((Red, Black), Earth),
+        ((Red, Red), Earth),
+        ((Red, Yellow), Magnetism),
+        ((Red, Blue), Ice),
+
+        ((Yellow, Black), Electricity),
+        ((Yellow, Red), Metal),
+        ((Yellow, Yellow), Electricity),
+        ((Yellow, Blue), Plant),
+
+        ((Blue, Black), Water),
+        ((Blue, Red), Lava),
+        ((Blue, Yellow), Fire),
+        ((Blue, Blue), Water),
+    ];
+
+    assert_eq!(table.len(), ManaColor::ALL.len() * ManaColor::ALL.len());
+    for ((a, b), element) in table {
+        assert_eq!(MagicElement::from_colors(a, b), element,
+            "{:?} + {:?}", a, b);
+    }
+}
+
+#[test]
+fn color_order_matters() {
+    use ManaColor::*;
+
+    for a in [Red, Yellow, Blue] {
+        for b in [Red, Yellow, Blue] {
+            if a != b {
+                assert_ne!(MagicElement::from_colors(a, b),
+                    MagicElement::from_colors(b, a));
+            }
+        }
+    }
+}
+
+#[test]
+fn new_casters_are_valid() {
+    for a in ManaColor::ALL {
+        for b in ManaColor::ALL {
+            assert!(MagicCaster::new(a, b, 10, 10).validate().is_ok());
+        }
+    }
+}
+
+#[test]
+fn mismatched_primary_is_caught() {
+    let mut caster = MagicCaster::new(ManaColor::Red, ManaColor::Blue, 10, 10);
+    assert_eq!(caster.primary, MagicElement::Ice);
+
+    caster.primary = MagicElement::Fire;
+    let err = caster.validate().unwrap_err();
+    assert_eq!(err.expected, MagicElement::Ice);
+    assert_eq!(err.found, MagicElement::Fire);
+}
+
+#[test]
+fn runes_round_trip() {
+    use magic_game::magic::runes::SpellRunes;
+
+    for src in [
+        "fireball: bolt fire speed 1.5 on hit damage 20 on hit status",
+        "mend: aura on hit heal 20 on cast mana 3",
+        "pit: sphere earth size 0.5 on hit carve",
+        "rampart: wall duration 2 on hit raise",
+    ] {
+        let runes = SpellRunes::parse(src).unwrap();
+        assert_eq!(runes.to_string(), src);
+        assert_eq!(SpellRunes::parse(&runes.to_string()).unwrap(), runes);
+        runes.compile().unwrap();
+    }
+}
+
+#[test]
+fn runes_report_errors() {
+    use magic_game::magic::runes::{parse_spell, RuneError};
+
+    assert!(matches!(
+        parse_spell("bad: bolt speed fast on hit damage 1"),
+        Err(RuneError::BadNumber { column: 17, .. })));
+    assert!(matches!(
+        parse_spell("bad: bolt on hit carve"),
+        Err(RuneError::NotAllowed { .. })));
+    assert!(matches!(
+        parse_spell("bad: bolt earth on hit status"),
+        Err(RuneError::NoStatus(MagicElement::Earth))));
+    assert!(matches!(
+        parse_spell("x: bolt on hit damage 4294967295 on hit damage 1"),
+        Err(RuneError::TooMuchDamage)));
+    assert!(matches!(
+        parse_spell("bad: cone fire on"),
+        Err(RuneError::UnexpectedEnd { .. })));
+}
+
+#[test]
+fn rune_costs_follow_the_element() {
+    use magic_game::magic::runes::parse_spell;
+
+    let fire = parse_spell("f: cone fire on hit damage 20").unwrap();
+    assert_eq!(fire.cost.black, 0);
+    assert_eq!(fire.cost.red, 0);
+    assert_eq!(fire.cost.blue + fire.cost.yellow, 10);
+
+    let any = parse_spell("a: cone on hit damage 20").unwrap();
+    assert_eq!(any.cost.black, 10);
+}
+
+#[test]
+fn shipped_spell_book_loads() {
+    use magic_game::magic::assets::SpellBook;
+
+    let src = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"), "/assets/spells/default.spells.ron"))
+        .unwrap();
+    let book = SpellBook::from_ron(&src).unwrap();
+    assert!(book.spells.iter().any(|s| s.name == "fireball"));
+}
+
+#[test]
+fn free_spells_are_rejected() {
+    use magic_game::magic::assets::{SpellBook, SpellBookError};
+    use magic_game::magic::spells::SpellError;
+
+    let src = br#"(spells: [(
+        name: "free",
+        element: None,
+        cost: (),
+        cast_time: 0.1,
+        cooldown: 0.1,
+        targeting: Caster,
+        effects: [Heal(amount: 100)],
+    )])"#;
+    assert!(matches!(
+        SpellBook::from_ron(src),
+        Err(SpellBookError::Invalid(_, SpellError::NoCost))));
+}
+
+#[test]
+fn oversized_shapes_are_rejected() {
+    use magic_game::magic::assets::{SpellBook, SpellBookError};
+    use magic_game::magic::spells::SpellError;
+
+    let book = |shape: &str| format!(r#"(spells: [(
+        name: "huge",
+        element: None,
+        cost: (black: 10),
+        cast_time: 0.1,
+        cooldown: 0.1,
+        targeting: Point(range: 10.0),
+        effects: [Terraform(shape: {shape}, carve: true)],
+    )])"#);
+
+    for shape in [
+        "Sphere(radius: 1e6)",
+        "Sphere(radius: -1.0)",
+        "Sphere(radius: NaN)",
+        "Cube(half_extent: 1000)",
+        "Cube(half_extent: -1)",
+        "Column(radius: 1.0, height: 100000)",
+        "Column(radius: inf, height: 4)",
+    ] {
+        assert!(matches!(
+            SpellBook::from_ron(book(shape).as_bytes()),
+            Err(SpellBookError::Invalid(_, SpellError::BadEffect(_)))),
+            "{shape} was let through");
+    }
+    assert!(SpellBook::from_ron(book("Sphere(radius: 2.5)").as_bytes())
+        .is_ok());
+}
+
+#[test]
+fn magnets_pull_and_push() {
+    use bevy::math::Vec3;
+    use magic_game::magic::magnet::{Falloff, MagneticField, MagnetMode};
+
+    let mut field = MagneticField {
+        mode: MagnetMode::Pull,
+        strength: 10.0,
+        radius: 5.0,
+        falloff: Falloff::Constant,
+    };
+    let body = Vec3::new(3.0, 0.0, 0.0);
+
+    let pull = field.acceleration(Vec3::ZERO, body, Vec3::ZERO);
+    assert!(pull.x < 0.0);
+    assert!((pull.length() - 10.0).abs() < 1e-4);
+
+    field.mode = MagnetMode::Push;
+    let push = field.acceleration(Vec3::ZERO, body, Vec3::ZERO);
+    assert!(push.x > 0.0);
+    assert_eq!(push, -pull);
+
+    // nothing past the radius
+    let far = Vec3::new(6.0, 0.0, 0.0);
+    assert_eq!(field.acceleration(Vec3::ZERO, far, Vec3::ZERO), Vec3::ZERO);
+}
+
+#[test]
+fn levitation_holds_bodies_up() {
+    use bevy::math::Vec3;
+    use magic_game::magic::magnet::{
+        Falloff, MagneticField, MagnetMode, GRAVITY};
+
+    let field = MagneticField {
+        mode: MagnetMode::Levitate,
+        strength: 10.0,
+        radius: 5.0,
+        falloff: Falloff::Constant,
+    };
+
+    // at rest in the middle it exactly cancels gravity
+    let still = field.acceleration(Vec3::ZERO, Vec3::ZERO, Vec3::ZERO);
+    assert_eq!(still, Vec3::Y * GRAVITY);
+
+    // below the centre it lifts harder than gravity pulls down
+    let below = Vec3::new(0.0, -2.0, 0.0);
+    assert!(field.acceleration(Vec3::ZERO, below, Vec3::ZERO).y > GRAVITY);
+}
+
+#[test]
+fn falloff_weakens_with_distance() {
+    use magic_game::magic::magnet::Falloff;
+
+    for falloff in [Falloff::Linear, Falloff::InverseSquare] {
+        let near = falloff.factor(1.0, 5.0);
+        let far = falloff.factor(4.0, 5.0);
+        assert!(near > far && far > 0.0, "{:?}", falloff);
+        assert_eq!(falloff.factor(5.5, 5.0), 0.0);
+    }
+    assert_eq!(Falloff::Constant.factor(4.0, 5.0), 1.0);
+    assert_eq!(Falloff::Linear.factor(0.0, 5.0), 1.0);
+}
+
+#[test]
+fn explosions_fade_towards_the_edge() {
+    use bevy::math::Vec3;
+    use magic_game::magic::explosion::Explosion;
+
+    let blast = Explosion {
+        center: Vec3::ZERO,
+        radius: 4.0,
+        element: MagicElement::Fire,
+        power: 3.0,
+        source: None,
+    };
+    assert_eq!(blast.falloff(Vec3::ZERO), 1.0);
+    assert_eq!(blast.falloff(Vec3::X * 2.0), 0.5);
+    assert_eq!(blast.falloff(Vec3::X * 5.0), 0.0);
+}
+
+#[test]
+fn lethal_hits_only_kill_once() {
+    use bevy::prelude::*;
+    use magic_game::magic::MagicElement;
+    use magic_game::magic::casting::InterruptCast;
+    use magic_game::magic::components::Health;
+    use magic_game::magic::damage::{apply_damage, DamageEvent, Died};
+
+    let mut app = App::new();
+    app
+        .add_event::<DamageEvent>()
+        .add_event::<Died>()
+        .add_event::<InterruptCast>()
+        .add_systems(Update, apply_damage);
+
+    let target = app.world.spawn(Health {
+        health: 10,
+        max_health: 10,
+        typed: MagicElement::NonElemental,
+    }).id();
+
+    for _ in 0..2 {
+        app.world.send_event(DamageEvent {
+            target,
+            amount: 50,
+            element: MagicElement::NonElemental,
+            source: None,
+        });
+    }
+    app.update();
+
+    assert_eq!(app.world.resource::<Events<Died>>().len(), 1);
+    assert_eq!(app.world.resource::<Events<InterruptCast>>().len(), 1);
+    assert_eq!(app.world.get::<Health>(target).unwrap().health, 0);
+}
+
+#[test]
+fn one_cast_per_caster_per_tick() {
+    use bevy::prelude::*;
+    use magic_game::magic::casting::{
+        start_casts, CastError, CastFailed, CastSpell, Casting, SpellTarget,
+    };
+    use magic_game::magic::spells::{
+        ManaCost, Spell, SpellEffect, Spells, Targeting,
+    };
+
+    let mut spells = Spells::default();
+    let mend = spells.add_spell(Spell {
+        name: "mend".to_owned(),
+        element: None,
+        cost: ManaCost { black: 10, ..default() },
+        cast_time: 1.0,
+        cooldown: 1.0,
+        targeting: Targeting::Caster,
+        effects: vec![SpellEffect::Heal { amount: 10 }],
+    });
+
+    let mut app = App::new();
+    app
+        .insert_resource(spells)
+        .add_event::<CastSpell>()
+        .add_event::<CastFailed>()
+        .add_systems(Update, start_casts);
+
+    let caster = app.world.spawn(
+        MagicCaster::new(ManaColor::Black, ManaColor::Red, 100, 100)).id();
+    for _ in 0..2 {
+        app.world.send_event(CastSpell {
+            caster,
+            spell: mend,
+            target: SpellTarget::Caster,
+        });
+    }
+    app.update();
+
+    let failed = app.world.resource::<Events<CastFailed>>();
+    let errors: Vec<_> = failed.iter_current_update_events()
+        .map(|f| f.error)
+        .collect();
+    assert_eq!(errors, [CastError::AlreadyCasting]);
+    assert!(app.world.get::<Casting>(caster).is_some());
+}
+
+#[test]
+fn effects_that_do_nothing_are_rejected() {
+    use magic_game::magic::assets::{SpellBook, SpellBookError};
+    use magic_game::magic::spells::SpellError;
+
+    for effect in ["Heal(amount: 0)", "Damage(amount: 0)"] {
+        let src = format!(r#"(spells: [(
+            name: "nothing",
+            element: None,
+            cost: (black: 10),
+            cast_time: 0.1,
+            cooldown: 0.1,
+            targeting: Caster,
+            effects: [{effect}],
+        )])"#);
+        assert!(matches!(
+            SpellBook::from_ron(src.as_bytes()),
+            Err(SpellBookError::Invalid(_, SpellError::BadEffect(_)))));
+    }
+}
+
+#[test]
+fn terraforms_need_an_element_with_rules() {
+    use bevy::prelude::*;
+    use magic_game::magic::casting::{
+        start_casts, CastError, CastFailed, CastSpell, SpellTarget,
+    };
+    use magic_game::magic::spells::{
+        ManaCost, Spell, SpellEffect, Spells, Targeting,
+    };
+    use magic_game::voxel::edit::VoxelShape;
+
+    let mut spells = Spells::default();
+    let raise = spells.add_spell(Spell {
+        name: "raise".to_owned(),
+        element: None,
+        cost: ManaCost { black: 10, ..default() },
+        cast_time: 1.0,
+        cooldown: 1.0,
+        targeting: Targeting::Caster,
+        effects: vec![SpellEffect::Terraform {
+            shape: VoxelShape::Sphere { radius: 2.0 },
+            carve: false,
+        }],
+    });
+
+    let mut app = App::new();
+    app
+        .insert_resource(spells)
+        .add_event::<CastSpell>()
+        .add_event::<CastFailed>()
+        .add_systems(Update, start_casts);
+
+    // plant can't raise the ground, earth can
+    let plant = app.world.spawn(
+        MagicCaster::new(ManaColor::Yellow, ManaColor::Blue, 100, 100)).id();
+    let earth = app.world.spawn(
+        MagicCaster::new(ManaColor::Red, ManaColor::Red, 100, 100)).id();
+    for caster in [plant, earth] {
+        app.world.send_event(CastSpell {
+            caster,
+            spell: raise,
+            target: SpellTarget::Caster,
+        });
+    }
+    app.update();
+
+    let failed = app.world.resource::<Events<CastFailed>>();
+    let failures: Vec<_> = failed.iter_current_update_events()
+        .map(|f| (f.caster, f.error))
+        .collect();
+    assert_eq!(failures, [(plant, CastError::WrongElement)]);
+}
@@ -0,0 +1,132 @@
+use bevy::math::Vec3;
+use magic_game::client_plugin::movement::{
+    MoveInput, MoveMode, PlayerMovement, JUMP_SPEED, WALK_SPEED};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn input(wish: Vec3) -> MoveInput {
+    MoveInput {
+        wish,
+        jump: false,
+        sprint: false,
+        crouch: false,
+        speed: 1.0,
+        headroom: true,
+    }
+}
+
+#[test]
+fn jumps_come_back_down() {
+    let mut movement = PlayerMovement::default();
+    let jump = MoveInput { jump: true, ..input(Vec3::ZERO) };
+
+    let mut height = movement.step(jump, true, DT).y;
+    assert!(height > 0.0);
+    assert!(movement.velocity.y < JUMP_SPEED);
+
+    let mut peaked = false;
+    for _ in 0..600 {
+        let step = movement.step(input(Vec3::ZERO), false, DT);
+        peaked |= step.y < 0.0;
+        height += step.y;
+        if height <= 0.0 {
+            break;
+        }
+    }
+    assert!(peaked && height <= 0.0);
+
+    // holding jump in the air does nothing
+    let before = movement.velocity.y;
+    movement.step(jump, false, DT);
+    assert!(movement.velocity.y < before);
+}
+
+#[test]
+fn flying_ignores_gravity() {
+    let mut movement = PlayerMovement::default();
+    movement.toggle_mode();
+    assert_eq!(movement.mode, MoveMode::Fly);
+
+    assert_eq!(movement.step(input(Vec3::ZERO), false, DT), Vec3::ZERO);
+    assert!(movement.step(input(Vec3::Y), false, DT).y > 0.0);
+}
+
+#[test]
+fn air_control_is_weaker() {
+    let forward = input(Vec3::NEG_Z);
+
+    let mut ground = PlayerMovement::default();
+    let mut air = PlayerMovement::default();
+    ground.step(forward, true, DT);
+    air.step(forward, false, DT);
+    assert!(ground.velocity.z < air.velocity.z && air.velocity.z < 0.0);
+
+    // sprinting goes faster than walking, crouching slower
+    for _ in 0..120 {
+        ground.step(forward, true, DT);
+    }
+    assert!((ground.velocity.z + WALK_SPEED).abs() < 0.01);
+    let sprint = MoveInput { sprint: true, ..forward };
+    let crouch = MoveInput { crouch: true, ..forward };
+    let mut fast = ground.clone();
+    let mut slow = ground.clone();
+    for _ in 0..120 {
+        fast.step(sprint, true, DT);
+        slow.step(crouch, true, DT);
+    }
+    assert!(fast.velocity.z < -WALK_SPEED);
+    assert!(slow.velocity.z > -WALK_SPEED);
+}
+
+#[test]
+fn pitch_stops_short_of_straight_up() {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+    use bevy::math::Quat;
+    use bevy::transform::components::Transform;
+    use magic_game::client_plugin::camera::{CameraRig, MAX_PITCH};
+    use magic_game::client_plugin::movement::EYE_HEIGHT;
+
+    let mut rig = CameraRig::default();
+    for _ in 0..100 {
+        rig.add_pitch(0.1);
+    }
+    assert_eq!(rig.pitch, MAX_PITCH);
+    rig.add_pitch(-100.0);
+    assert_eq!(rig.pitch, -MAX_PITCH);
+
+    // aiming follows both the camera's pitch and the body's yaw
+    rig.pitch = FRAC_PI_4;
+    let body = Transform::from_rotation(Quat::from_rotation_y(FRAC_PI_2));
+    let (eye, dir) = rig.aim(&body);
+    assert_eq!(eye, Vec3::Y * EYE_HEIGHT);
+    assert!(dir.x < -0.7 && dir.y > 0.7 && dir.z.abs() < 1e-4);
+}
+
+#[test]
+fn crouching_waits_for_headroom() {
+    let mut movement = PlayerMovement::default();
+    let crouch = MoveInput { crouch: true, ..input(Vec3::ZERO) };
+    movement.step(crouch, true, DT);
+    assert!(movement.crouching);
+
+    // letting go under something low keeps them down, and can't jump
+    let stuck = MoveInput { jump: true, headroom: false, ..input(Vec3::ZERO) };
+    movement.step(stuck, true, DT);
+    assert!(movement.crouching);
+    assert!(movement.velocity.y <= 0.0);
+
+    movement.step(input(Vec3::ZERO), true, DT);
+    assert!(!movement.crouching);
+}
+
+#[test]
+fn ceilings_stop_jumps() {
+    let mut movement = PlayerMovement::default();
+    let jump = MoveInput { jump: true, ..input(Vec3::ZERO) };
+    movement.step(jump, true, DT);
+    assert!(movement.velocity.y > 0.0);
+
+    movement.hit_ceiling();
+    assert_eq!(movement.velocity.y, 0.0);
+    assert!(movement.step(input(Vec3::ZERO), false, DT).y < 0.0);
+}
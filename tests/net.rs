@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use magic_game::net::client::ServerInfo;
+use magic_game::net::handshake::{
+    DisconnectReason, LoadingRegistries, Registries,
+};
+use magic_game::net::protocol::{MessageUsi, PlayerId};
+
+mod common;
+use common::harness::{HarnessConfig, NetHarness};
+
+#[test]
+fn clients_connect_and_handshake() {
+    let mut h = NetHarness::new(HarnessConfig::default().with_clients(2));
+    assert!(h.connect(200));
+}
+
+#[test]
+fn players_are_replicated_to_every_client() {
+    let mut h = NetHarness::new(HarnessConfig::default().with_clients(2));
+    assert!(h.connect(200));
+
+    let replicated = h.step_until(200, |h| {
+        (0..2).all(|i| h.client_components::<PlayerId>(i).len() == 2)
+    });
+    assert!(replicated);
+
+    let players = h.server_components::<PlayerId>();
+    assert_eq!(players.len(), h.client_ids.len());
+    for id in h.client_ids.iter() {
+        assert!(players.iter().any(|p| p.0 == *id));
+    }
+}
+
+#[test]
+fn mismatched_registries_are_rejected() {
+    let mut h = NetHarness::new(HarnessConfig::default());
+    h.client(0).world.resource_mut::<Registries>().insert("voxels", 0);
+    assert!(!h.connect(50));
+
+    let reason = h.client(0).world.get_resource::<DisconnectReason>();
+    assert_eq!(reason, Some(&DisconnectReason::RegistryMismatch {
+        registry: "voxels".to_owned(),
+    }));
+}
+
+#[test]
+fn handshakes_wait_for_registries_to_load() {
+    let mut h = NetHarness::new(HarnessConfig::default());
+    h.server.world.resource_mut::<LoadingRegistries>().0
+        .insert("spells".to_owned());
+    assert!(!h.connect(20));
+
+    h.server.world.resource_mut::<LoadingRegistries>().0.clear();
+    assert!(h.step_until(50, |h| {
+        h.client(0).world.contains_resource::<ServerInfo>()
+    }));
+}
+
+#[test]
+fn messages_survive_a_bad_link() {
+    let config = HarnessConfig::default()
+        .with_conditioner(
+            Duration::from_millis(80), Duration::from_millis(20), 0.1);
+    let mut h = NetHarness::new(config);
+    h.record_messages::<MessageUsi>();
+    assert!(h.connect(400));
+
+    h.client(0).world
+        .resource_mut::<magic_game::client::ConnectionManager>()
+        .send_message::<magic_game::net::protocol::MyChannel, _>(
+            &MessageUsi(7))
+        .unwrap();
+
+    let mut received = Vec::new();
+    let arrived = h.step_until(200, |h| {
+        received.extend(h.server_received::<MessageUsi>());
+        !received.is_empty()
+    });
+    assert!(arrived);
+
+    let (from, MessageUsi(n)) = received[0].clone();
+    assert_eq!(from, h.client_ids[0]);
+    assert_eq!(n, 7);
+}
@@ -0,0 +1,65 @@
+use magic_game::magic::{ManaColor, MagicElement};
+use magic_game::magic::casting::Cooldowns;
+use magic_game::magic::components::MagicCaster;
+use magic_game::magic::spells::{register_default_spells, Spells};
+use magic_game::npc::NpcState;
+use magic_game::npc::brain::{next_state, pick_spell, NpcBrain, Senses};
+
+fn senses(distance: Option<f32>, health: f32, mana: f32) -> Senses {
+    Senses {
+        distance,
+        target_element: MagicElement::Water,
+        health,
+        mana,
+    }
+}
+
+#[test]
+fn npcs_chase_attack_and_retreat() {
+    let brain = NpcBrain::default();
+    let idle = NpcState::Idle;
+
+    assert_eq!(next_state(idle, &brain, &senses(None, 1.0, 1.0)),
+        NpcState::Idle);
+    assert_eq!(next_state(idle, &brain, &senses(Some(25.0), 1.0, 1.0)),
+        NpcState::Chase);
+    assert_eq!(next_state(idle, &brain, &senses(Some(10.0), 1.0, 1.0)),
+        NpcState::Attack);
+    assert_eq!(next_state(idle, &brain, &senses(Some(10.0), 1.0, 0.1)),
+        NpcState::Retreat);
+
+    // retreating npcs wait until they've properly recovered
+    let retreat = NpcState::Retreat;
+    assert_eq!(next_state(retreat, &brain, &senses(Some(10.0), 1.0, 0.5)),
+        NpcState::Retreat);
+    assert_eq!(next_state(retreat, &brain, &senses(Some(10.0), 1.0, 0.9)),
+        NpcState::Attack);
+}
+
+#[test]
+fn npcs_pick_effective_spells() {
+    let mut spells = Spells::default();
+    register_default_spells(&mut spells);
+    let slots: Vec<_> = ["spark", "bolt", "mend"].iter()
+        .map(|name| spells.id_from_name(name).unwrap())
+        .collect();
+    let [spark, bolt, mend] = [slots[0], slots[1], slots[2]];
+
+    // a fire caster's bolts are weak against water, but spark isn't
+    let caster = MagicCaster::new(ManaColor::Blue, ManaColor::Yellow, 60, 60);
+    let pick = |cooldowns: Option<&Cooldowns>, s: Senses|
+        pick_spell(&spells, &slots, &caster, cooldowns, &s);
+
+    assert_eq!(pick(None, senses(Some(10.0), 1.0, 1.0)), Some(spark));
+    // out of spark's range
+    assert_eq!(pick(None, senses(Some(40.0), 1.0, 1.0)), Some(bolt));
+    // hurt badly enough to heal instead
+    assert_eq!(pick(None, senses(Some(10.0), 0.3, 1.0)), Some(mend));
+
+    let mut cooldowns = Cooldowns::default();
+    cooldowns.0.insert(spark, 1.0);
+    assert_eq!(pick(Some(&cooldowns), senses(Some(10.0), 1.0, 1.0)),
+        Some(bolt));
+
+    assert_eq!(pick(None, senses(None, 1.0, 1.0)), None);
+}
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use bevy::math::IVec3;
+use magic_game::voxel::debris::{find_islands, Support, VoxelDebris};
+use magic_game::voxel::path::{find_path, Agent, Terrain, MAX_FALL};
+use magic_game::voxel::path::STEP_HEIGHT;
+
+// a world with solid ground at y = 0 and nothing loaded below it
+fn world(solid: &HashSet<IVec3>) -> impl Fn(IVec3) -> Support + '_ {
+    move |p| {
+        if p.y < 0 {
+            Support::Anchored
+        } else if solid.contains(&p) {
+            Support::Loose
+        } else {
+            Support::Empty
+        }
+    }
+}
+
+#[test]
+fn cut_pillars_fall() {
+    // a pillar with its second voxel carved out
+    let solid: HashSet<_> = (0..5)
+        .filter(|&y| y != 1)
+        .map(|y| IVec3::new(0, y, 0))
+        .collect();
+
+    let islands = find_islands(&[IVec3::new(0, 1, 0)], world(&solid));
+    assert_eq!(islands.len(), 1);
+
+    let mut island = islands[0].clone();
+    island.sort_by_key(|p| p.y);
+    assert_eq!(island, (2..5).map(|y| IVec3::new(0, y, 0)).collect::<Vec<_>>());
+}
+
+#[test]
+fn connected_terrain_stays_up() {
+    // an arch: two pillars joined at the top, with one side's base carved
+    let mut solid: HashSet<_> = (0..4)
+        .flat_map(|y| [IVec3::new(0, y, 0), IVec3::new(3, y, 0)])
+        .collect();
+    solid.extend((1..3).map(|x| IVec3::new(x, 3, 0)));
+    solid.remove(&IVec3::new(0, 1, 0));
+
+    assert!(find_islands(&[IVec3::new(0, 1, 0)], world(&solid)).is_empty());
+}
+
+#[test]
+fn islands_are_only_found_once() {
+    let solid: HashSet<_> = [IVec3::new(0, 2, 0), IVec3::new(1, 2, 0)]
+        .into_iter()
+        .collect();
+
+    // both removed voxels touch the same floating pair
+    let removed = [IVec3::new(0, 1, 0), IVec3::new(1, 1, 0)];
+    let islands = find_islands(&removed, world(&solid));
+    assert_eq!(islands.len(), 1);
+    assert_eq!(islands[0].len(), 2);
+}
+
+#[test]
+fn debris_never_replaces_water() {
+    use bevy::transform::components::{GlobalTransform, Transform};
+    use magic_game::voxel::{register_default_voxels, Voxels};
+
+    let mut voxels = Voxels::default();
+    register_default_voxels(&mut voxels);
+    let stone = voxels.id_from_name("stone").unwrap();
+
+    let (debris, centre) = VoxelDebris::from_voxels(&[
+        (IVec3::new(0, 1, 0), stone),
+        (IVec3::new(0, 2, 0), stone),
+    ]).unwrap();
+    let trans = GlobalTransform::from(Transform::from_translation(centre));
+
+    // come to rest in a puddle two voxels deep
+    let water = |p: IVec3| p.x == 0 && p.z == 0 && (1..=2).contains(&p.y);
+    let placed = debris.placement(&trans, |p| p.y > 0 && !water(p));
+    assert_eq!(placed, Some(vec![
+        (IVec3::new(0, 3, 0), stone),
+        (IVec3::new(0, 4, 0), stone),
+    ]));
+
+    // buried with no room at all, so it has to stay as debris
+    assert_eq!(debris.placement(&trans, |_| false), None);
+}
+
+#[test]
+fn rays_visit_every_voxel_once() {
+    use bevy::math::Vec3;
+    use magic_game::voxel::{voxel_ray, VOXEL_SIZE};
+
+    let straight = voxel_ray(Vec3::splat(0.1), Vec3::new(2.1, 0.1, 0.1));
+    let expected: Vec<_> = (0..=(2.1 / VOXEL_SIZE) as i32)
+        .map(|x| IVec3::new(x, 0, 0))
+        .collect();
+    assert_eq!(straight, expected);
+
+    let from = Vec3::new(0.3, -1.2, 4.7);
+    let to = Vec3::new(-3.4, 2.9, 1.1);
+    let ray = voxel_ray(from, to);
+    assert_eq!(ray.first(), Some(&(from / VOXEL_SIZE).floor().as_ivec3()));
+    assert_eq!(ray.last(), Some(&(to / VOXEL_SIZE).floor().as_ivec3()));
+
+    // each step moves to a face neighbour
+    for pair in ray.windows(2) {
+        let step = (pair[1] - pair[0]).abs();
+        assert_eq!(step.x + step.y + step.z, 1);
+    }
+}
+
+struct Blocks(HashSet<IVec3>);
+
+impl Terrain for Blocks {
+    fn solid(&self, pos: IVec3) -> bool {
+        self.0.contains(&pos)
+    }
+}
+
+// a strip of floor along x, `y` high, from x = `from` to `to`
+fn floor(blocks: &mut Blocks, from: i32, to: i32, y: i32) {
+    for x in from..=to {
+        for z in -2..=2 {
+            blocks.0.insert(IVec3::new(x, y, z));
+        }
+    }
+}
+
+fn agent() -> Agent {
+    Agent::new(2.0, STEP_HEIGHT, MAX_FALL)
+}
+
+#[test]
+fn paths_climb_single_steps() {
+    let mut blocks = Blocks(HashSet::new());
+    floor(&mut blocks, 0, 10, 0);
+    floor(&mut blocks, 5, 10, 1);
+
+    let start = IVec3::new(0, 1, 0);
+    let goal = IVec3::new(9, 2, 0);
+    let path = find_path(&blocks, agent(), start, goal).unwrap();
+    assert_eq!(path.first(), Some(&start));
+    assert_eq!(path.last(), Some(&goal));
+    assert!(path.windows(2).all(|w| w[1].y - w[0].y <= agent().step));
+
+    // two voxels is too high to step up
+    floor(&mut blocks, 5, 10, 2);
+    let goal = IVec3::new(9, 3, 0);
+    assert_eq!(find_path(&blocks, agent(), start, goal), None);
+}
+
+#[test]
+fn paths_need_headroom() {
+    let mut blocks = Blocks(HashSet::new());
+    floor(&mut blocks, 0, 10, 0);
+    let start = IVec3::new(0, 1, 0);
+    let goal = IVec3::new(10, 1, 0);
+
+    // a tunnel just too low for the agent
+    floor(&mut blocks, 4, 6, agent().height);
+    assert_eq!(find_path(&blocks, agent(), start, goal), None);
+
+    let mut blocks = Blocks(HashSet::new());
+    floor(&mut blocks, 0, 10, 0);
+    floor(&mut blocks, 4, 6, agent().height + 1);
+    assert!(find_path(&blocks, agent(), start, goal).is_some());
+}
+
+#[test]
+fn paths_drop_off_ledges() {
+    // a ledge with ground a safe distance below it
+    let mut blocks = Blocks(HashSet::new());
+    floor(&mut blocks, 0, 4, 10);
+    floor(&mut blocks, 5, 10, 4);
+    let start = IVec3::new(0, 11, 0);
+    let path = find_path(&blocks, agent(), start, IVec3::new(9, 5, 0));
+    assert!(path.is_some());
+
+    // and one too far down to jump
+    let mut blocks = Blocks(HashSet::new());
+    floor(&mut blocks, 0, 4, 10);
+    floor(&mut blocks, 5, 10, 0);
+    let path = find_path(&blocks, agent(), start, IVec3::new(9, 1, 0));
+    assert_eq!(path, None);
+}
+
+#[test]
+fn tracked_paths_notice_edits() {
+    use bevy::ecs::system::{CommandQueue, Commands};
+    use bevy::ecs::world::World;
+    use magic_game::voxel::path::PathFollower;
+    use magic_game::voxel::{register_default_voxels, VoxelId, Voxels};
+
+    let mut voxels = Voxels::default();
+    register_default_voxels(&mut voxels);
+    let stone = voxels.id_from_name("stone").unwrap();
+
+    let mut world = World::new();
+    let mut queue = CommandQueue::default();
+    voxels.add_chunk(Commands::new(&mut queue, &world), 0, 0, 0);
+    queue.apply(&mut world);
+
+    let path = vec![IVec3::new(1, 1, 1), IVec3::new(2, 1, 1)];
+    let follower = PathFollower::tracked(&voxels, agent(), path);
+    assert_eq!(follower.goal(), Some(IVec3::new(2, 1, 1)));
+    assert!(follower.unchanged(&voxels));
+
+    assert!(voxels.edit_block(5, 0, 5, stone));
+    assert!(!follower.unchanged(&voxels));
+
+    // untracked paths have nothing to go stale
+    let untracked = PathFollower::new(vec![IVec3::new(1, 1, 1)]);
+    assert!(voxels.edit_block(5, 0, 5, VoxelId::air()));
+    assert!(untracked.unchanged(&voxels));
+}
+
+#[test]
+fn boxed_in_plants_let_their_chunk_sleep() {
+    use bevy::ecs::system::{CommandQueue, Commands};
+    use bevy::ecs::world::World;
+    use magic_game::voxel::sim::VoxelSim;
+    use magic_game::voxel::{register_default_voxels, Voxels};
+
+    let mut voxels = Voxels::default();
+    register_default_voxels(&mut voxels);
+    let stone = voxels.id_from_name("stone").unwrap();
+    let plant = voxels.id_from_name("plant").unwrap();
+
+    let mut world = World::new();
+    let mut queue = CommandQueue::default();
+    voxels.add_chunk(Commands::new(&mut queue, &world), 0, 0, 0);
+    queue.apply(&mut world);
+
+    // a plant on a pillar, with nothing beside it to grow onto
+    voxels.edit_block(4, 0, 4, stone);
+    voxels.edit_block(4, 1, 4, plant);
+    assert!(voxels.is_active(0, 0, 0));
+
+    let mut sim = VoxelSim::default();
+    sim.step(&mut voxels);
+    assert!(!voxels.is_active(0, 0, 0));
+
+    // ground next to it gives it somewhere to go
+    voxels.edit_block(5, 0, 4, stone);
+    sim.step(&mut voxels);
+    assert!(voxels.is_active(0, 0, 0));
+}